@@ -1,10 +1,20 @@
+//! Elementwise arithmetic over tensors. In particular, `*` on tensors
+//! is the elementwise (Hadamard) product, also named [`Tensor::hadamard`],
+//! *not* the matrix product. That is `*` on [`Matrix`], a distinct type,
+//! which calls [`Matrix::multiply`], so the two can't be confused.
+//!
+//! [`Matrix`]: crate::Matrix
+//! [`Matrix::multiply`]: crate::Matrix::multiply
+
+use core::cmp::Ordering;
 use core::ops;
 
-use crate::space::Tensor;
+use crate::invar::Int;
+use crate::space::{BroadcastErr, Tensor};
 
 impl<S> core::ops::Add for Tensor<S>
 where
-    S: ops::Add<Output = S>,
+    S: ops::Add<Output = S> + Copy,
 {
     type Output = Self;
 
@@ -12,18 +22,20 @@ where
     /// consistent with the principles of linear algebra. If the RHS has
     /// fewer dimensions than the LHS, RHS will be repeated for each of
     /// those dimensions; this has some memory implications, but minor.
+    ///
+    /// The RHS is repeated as a flat, column-major sequence, so it only
+    /// lines up with the LHS if its length divides the LHS length. This
+    /// is checked in debug builds only: use [`Tensor::try_add`] to have
+    /// it checked always, or [`Tensor::broadcast_to`] to be explicit.
     fn add(self, rhs: Self) -> Self::Output {
         // Naive implementation. We attempt to exploit processor features before this.
         if let (Some(lhs_d), Some(rhs_d)) = (self.data(), rhs.data()) {
-            let (lhs_d) = match self.data() {
-                Some(d) => d,
-                None => panic!("could not obtain `.data` on lhs"),
-            };
-
-            let (rhs_d) = match rhs.data() {
-                Some(d) => d,
-                None => panic!("could not obtain `.data` on rhs"),
-            };
+            debug_assert!(
+                lhs_d.len() % rhs_d.len() == 0,
+                "cannot repeat a tensor of {} elements over one of {}",
+                rhs_d.len(),
+                lhs_d.len()
+            );
 
             Tensor::<S> {
                 data: Some(
@@ -43,27 +55,31 @@ where
 
 impl<S> core::ops::Mul for Tensor<S>
 where
-    S: ops::Mul<Output = S>,
+    S: ops::Mul<Output = S> + Copy,
 {
     type Output = Self;
 
-    /// Returns a tensor of the same proportions as the LHS, not the RHS,
+    /// The elementwise (Hadamard) product, *not* the matrix product, as
+    /// [`Tensor::hadamard`] returns. See there for the proportions.
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.hadamard(rhs)
+    }
+}
+
+impl<S> Tensor<S>
+where
+    S: ops::Mul<Output = S> + Copy,
+{
+    /// Multiplies elementwise, i.e. returns the Hadamard product, which
+    /// is what `*` on tensors returns too. For the matrix product, use
+    /// [`crate::Matrix::multiply`], or `*` on matrices. This returns a
+    /// tensor of the same proportions as the LHS, not the RHS,
     /// consistent with the principles of linear algebra. If the RHS has
     /// fewer dimensions than the LHS, RHS will be repeated for each of
     /// those dimensions; this has some memory implications, but minor.
-    fn mul(self, rhs: Self) -> Self::Output {
+    pub fn hadamard(self, rhs: Self) -> Self {
         // Naive implementation. We attempt to exploit processor features before this.
         if let (Some(lhs_d), Some(rhs_d)) = (self.data(), rhs.data()) {
-            let (lhs_d) = match self.data() {
-                Some(d) => d,
-                None => panic!("missing tensor data on lhs"),
-            };
-
-            let (rhs_d) = match rhs.data() {
-                Some(d) => d,
-                None => panic!("missing tensor data on rhs"),
-            };
-
             Tensor::<S> {
                 data: Some(
                     lhs_d
@@ -79,3 +95,314 @@ where
         }
     }
 }
+
+impl<S> core::ops::AddAssign for Tensor<S>
+where
+    S: ops::Add<Output = S> + Copy,
+{
+    /// Adds the RHS into this tensor in place, repeating it as [`Add`]
+    /// does, so that no new buffer is allocated. If this tensor has no
+    /// data, this is a no-op. If only the RHS has none, this panics.
+    ///
+    /// [`Add`]: core::ops::Add
+    fn add_assign(&mut self, rhs: Self) {
+        if let Some(lhs_d) = &mut self.data {
+            let rhs_d = rhs.data().expect("missing tensor data on rhs");
+            debug_assert!(
+                lhs_d.len() % rhs_d.len() == 0,
+                "cannot repeat a tensor of {} elements over one of {}",
+                rhs_d.len(),
+                lhs_d.len()
+            );
+
+            lhs_d
+                .iter_mut()
+                .zip(rhs_d.iter().cycle())
+                .for_each(|(s1, &s2)| *s1 = *s1 + s2);
+        }
+    }
+}
+
+impl<S> core::ops::MulAssign for Tensor<S>
+where
+    S: ops::Mul<Output = S> + Copy,
+{
+    /// Multiplies this tensor by the RHS in place. See [`AddAssign`] for
+    /// the repetition, allocation, and missing data characteristics.
+    ///
+    /// [`AddAssign`]: core::ops::AddAssign
+    fn mul_assign(&mut self, rhs: Self) {
+        if let Some(lhs_d) = &mut self.data {
+            let rhs_d = rhs.data().expect("missing tensor data on rhs");
+            debug_assert!(
+                lhs_d.len() % rhs_d.len() == 0,
+                "cannot repeat a tensor of {} elements over one of {}",
+                rhs_d.len(),
+                lhs_d.len()
+            );
+
+            lhs_d
+                .iter_mut()
+                .zip(rhs_d.iter().cycle())
+                .for_each(|(s1, &s2)| *s1 = *s1 * s2);
+        }
+    }
+}
+
+impl<S> core::ops::Sub for Tensor<S>
+where
+    S: ops::Sub<Output = S> + Copy,
+{
+    type Output = Self;
+
+    /// Returns a tensor of the same proportions as the LHS, not the RHS,
+    /// consistent with the principles of linear algebra. If the RHS has
+    /// fewer dimensions than the LHS, RHS will be repeated for each of
+    /// those dimensions. Two tensors without data yield the same.
+    fn sub(self, rhs: Self) -> Self::Output {
+        // Naive implementation. We attempt to exploit processor features before this.
+        match (self.data(), rhs.data()) {
+            (Some(lhs_d), Some(rhs_d)) => Tensor::<S> {
+                data: Some(
+                    lhs_d
+                        .iter()
+                        .zip(rhs_d.iter().cycle())
+                        .map(|(&s1, &s2)| s1 - s2)
+                        .collect(),
+                ),
+                dims: self.dims(),
+            },
+            (None, None) => Tensor::<S> { data: None, dims: self.dims() },
+            (None, _) => panic!("missing tensor data on lhs"),
+            (_, None) => panic!("missing tensor data on rhs"),
+        }
+    }
+}
+
+impl<S> core::ops::Div for Tensor<S>
+where
+    S: ops::Div<Output = S> + Copy,
+{
+    type Output = Self;
+
+    /// Returns a tensor of the same proportions as the LHS, repeating
+    /// the RHS as addition does. Integer division by zero will
+    /// panic, as it does for the scalar types: if you can't rule that
+    /// out, use [`Tensor::checked_div`] instead.
+    fn div(self, rhs: Self) -> Self::Output {
+        // Naive implementation. We attempt to exploit processor features before this.
+        match (self.data(), rhs.data()) {
+            (Some(lhs_d), Some(rhs_d)) => Tensor::<S> {
+                data: Some(
+                    lhs_d
+                        .iter()
+                        .zip(rhs_d.iter().cycle())
+                        .map(|(&s1, &s2)| s1 / s2)
+                        .collect(),
+                ),
+                dims: self.dims(),
+            },
+            (None, None) => Tensor::<S> { data: None, dims: self.dims() },
+            (None, _) => panic!("missing tensor data on lhs"),
+            (_, None) => panic!("missing tensor data on rhs"),
+        }
+    }
+}
+
+impl<S> Tensor<S>
+where
+    S: ops::Div<Output = S> + Copy + Default + PartialEq,
+{
+    /// Divides elementwise like [`core::ops::Div`], with the same LHS
+    /// proportions and RHS repetition, but returns `None` if any RHS
+    /// element is zero (i.e. [`Default`]) rather than panicking. Note
+    /// this also applies to floats, which would otherwise give `inf`.
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        match (self.data(), rhs.data()) {
+            (Some(lhs_d), Some(rhs_d)) => {
+                if rhs_d.iter().any(|&s| s == S::default()) {
+                    return None;
+                }
+
+                Some(Tensor::<S> {
+                    data: Some(
+                        lhs_d
+                            .iter()
+                            .zip(rhs_d.iter().cycle())
+                            .map(|(&s1, &s2)| s1 / s2)
+                            .collect(),
+                    ),
+                    dims: self.dims(),
+                })
+            }
+            (None, None) => Some(Tensor::<S> { data: None, dims: self.dims() }),
+            _ => None,
+        }
+    }
+}
+
+impl<S> Tensor<S>
+where
+    S: ops::Add<Output = S> + Copy,
+{
+    /// Adds elementwise like [`core::ops::Add`], with the same LHS
+    /// proportions and RHS repetition, but returns an error if the RHS
+    /// length doesn't divide the LHS length, rather than wrapping round
+    /// partway through the RHS and silently misaligning the two.
+    pub fn try_add(self, rhs: Self) -> Result<Self, BroadcastErr> {
+        match (self.data(), rhs.data()) {
+            (Some(lhs_d), Some(rhs_d)) if lhs_d.len() % rhs_d.len() != 0 => {
+                Err(BroadcastErr::Indivisible { lhs: lhs_d.len(), rhs: rhs_d.len() })
+            }
+            (Some(_), Some(_)) => Ok(self + rhs),
+            (None, None) => Ok(Tensor::<S> { data: None, dims: self.dims() }),
+            (None, _) => panic!("missing tensor data on lhs"),
+            (_, None) => panic!("missing tensor data on rhs"),
+        }
+    }
+}
+
+/// ## Scalar ops
+impl<S> Tensor<S>
+where
+    S: Copy,
+{
+    /// Multiplies every element by the scalar `k`, leaving the shape
+    /// as is. This reuses the tensor's own buffer, so it allocates no
+    /// memory, unlike multiplying by a second tensor filled with `k`.
+    pub fn scalar_mul(mut self, k: S) -> Tensor<S>
+    where
+        S: ops::Mul<Output = S>,
+    {
+        if let Some(d) = &mut self.data {
+            d.iter_mut().for_each(|s| *s = *s * k);
+        }
+
+        self
+    }
+
+    /// Adds the scalar `k` to every element, leaving the shape as is.
+    /// See [`Tensor::scalar_mul`] for the allocation characteristics.
+    pub fn scalar_add(mut self, k: S) -> Tensor<S>
+    where
+        S: ops::Add<Output = S>,
+    {
+        if let Some(d) = &mut self.data {
+            d.iter_mut().for_each(|s| *s = *s + k);
+        }
+
+        self
+    }
+
+    /// Pins every element into `[min, max]`, e.g. before a `log`, or to
+    /// clip gradients, leaving the shape as is. NaNs are left as NaNs,
+    /// since they compare neither below `min` nor above `max`. This
+    /// panics if `min > max`. See [`Tensor::scalar_mul`] for allocation.
+    pub fn clamp(mut self, min: S, max: S) -> Tensor<S>
+    where
+        S: PartialOrd,
+    {
+        self.clamp_inplace(min, max);
+        self
+    }
+
+    /// Pins every element into `[min, max]` in place, as [`Tensor::clamp`]
+    /// does, for a tensor which is only borrowed.
+    pub fn clamp_inplace(&mut self, min: S, max: S)
+    where
+        S: PartialOrd,
+    {
+        assert!(
+            min.partial_cmp(&max) != Some(Ordering::Greater),
+            "cannot clamp to an empty range, as min > max"
+        );
+        self.apply_inplace(|s| {
+            if *s < min {
+                *s = min;
+            } else if *s > max {
+                *s = max;
+            }
+        });
+    }
+}
+
+/// ## Integer ops
+/// Each of these combines elementwise, with the same LHS proportions
+/// and RHS repetition as [`core::ops::Add`], but in an overflow mode
+/// which is the same in every build, unlike the operators, which panic
+/// on overflow in debug builds, but wrap in release.
+impl<S: Int> Tensor<S> {
+    /// Adds elementwise, clamping each sum to the bounds of `S`.
+    pub fn saturating_add(self, rhs: Self) -> Self {
+        zip_cycle(self, rhs, S::saturating_add)
+    }
+
+    /// Adds elementwise, wrapping each sum around the bounds of `S`.
+    pub fn wrapping_add(self, rhs: Self) -> Self {
+        zip_cycle(self, rhs, S::wrapping_add)
+    }
+
+    /// Adds elementwise, or returns `None` if any sum overflows.
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        try_zip_cycle(self, rhs, S::checked_add)
+    }
+
+    /// Multiplies elementwise, clamping each product to the bounds of `S`.
+    pub fn saturating_mul(self, rhs: Self) -> Self {
+        zip_cycle(self, rhs, S::saturating_mul)
+    }
+
+    /// Multiplies elementwise, wrapping each product around the bounds of `S`.
+    pub fn wrapping_mul(self, rhs: Self) -> Self {
+        zip_cycle(self, rhs, S::wrapping_mul)
+    }
+
+    /// Multiplies elementwise, or returns `None` if any product overflows.
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        try_zip_cycle(self, rhs, S::checked_mul)
+    }
+}
+
+/// Combine `lhs` and `rhs` elementwise with `f`, repeating the RHS as
+/// [`core::ops::Sub`] does. Two tensors without data yield the same.
+fn zip_cycle<S: Copy>(lhs: Tensor<S>, rhs: Tensor<S>, f: impl Fn(S, S) -> S) -> Tensor<S> {
+    match (lhs.data(), rhs.data()) {
+        (Some(lhs_d), Some(rhs_d)) => Tensor::<S> {
+            data: Some(
+                lhs_d
+                    .iter()
+                    .zip(rhs_d.iter().cycle())
+                    .map(|(&s1, &s2)| f(s1, s2))
+                    .collect(),
+            ),
+            dims: lhs.dims(),
+        },
+        (None, None) => Tensor::<S> { data: None, dims: lhs.dims() },
+        (None, _) => panic!("missing tensor data on lhs"),
+        (_, None) => panic!("missing tensor data on rhs"),
+    }
+}
+
+/// Combine `lhs` and `rhs` elementwise with `f`, as [`zip_cycle`] does,
+/// but returning `None` if `f` does for any pair of elements.
+fn try_zip_cycle<S: Copy>(
+    lhs: Tensor<S>,
+    rhs: Tensor<S>,
+    f: impl Fn(S, S) -> Option<S>,
+) -> Option<Tensor<S>> {
+    match (lhs.data(), rhs.data()) {
+        (Some(lhs_d), Some(rhs_d)) => Some(Tensor::<S> {
+            data: Some(
+                lhs_d
+                    .iter()
+                    .zip(rhs_d.iter().cycle())
+                    .map(|(&s1, &s2)| f(s1, s2))
+                    .collect::<Option<_>>()?,
+            ),
+            dims: lhs.dims(),
+        }),
+        (None, None) => Some(Tensor::<S> { data: None, dims: lhs.dims() }),
+        (None, _) => panic!("missing tensor data on lhs"),
+        (_, None) => panic!("missing tensor data on rhs"),
+    }
+}
@@ -0,0 +1,124 @@
+//! Inner and outer products of vectors. These are dispatched to AMX
+//! where it is available, and otherwise to plain scalar code.
+
+use core::{mem, ops};
+
+use super::matmul::multiply_slices;
+use crate::arch::amx::{with_amx, AmxCtx, AmxOps, AmxScalar, RegSet, DEPTH};
+use crate::invar::Float;
+use crate::space::{shape, Matrix, Tensor, Vector};
+
+impl<T: AmxScalar> Vector<T> {
+    /// Returns the dot product of this vector and another vector. The
+    /// vectors must be the same length, or this will panic. On AMX,
+    /// vectors of up to 8 registers' length (i.e. 256 halfs, or 128
+    /// floats) are multiplied in a single op.
+    pub fn dot(&self, other: &Vector<T>) -> T {
+        let (a, b) = (self.0.data().unwrap_or(&[]), other.0.data().unwrap_or(&[]));
+        assert_eq!(a.len(), b.len(), "cannot take the dot product of vectors of different lengths");
+
+        match with_amx(|amx| dot_amx(amx, a, b)) {
+            Ok(dot) => dot,
+            Err(_) => a
+                .iter()
+                .zip(b)
+                .fold(T::default(), |acc, (&x, &y)| acc + x * y),
+        }
+    }
+
+    /// Returns the outer product of this vector (of length M) and the
+    /// other (of length N), i.e. the MxN matrix whose element `(i, j)`
+    /// is `self[i] * other[j]`. This is the product of an Mx1 and a 1xN
+    /// matrix, which is exactly what AMX computes from one register of
+    /// each of X and Y, so it's multiplied as [`Matrix::multiply`] is.
+    pub fn outer(&self, other: &Vector<T>) -> Matrix<T> {
+        let (m, n) = (self.0.vlen(), other.0.vlen());
+        let data = match (self.0.data(), other.0.data()) {
+            (Some(a), Some(b)) => Some(multiply_slices(a, b, m, 1, n)),
+            _ => None,
+        };
+
+        Matrix(Tensor { data, dims: shape(&[m, n]) })
+    }
+}
+
+impl<T: AmxScalar + Float> Vector<T> {
+    /// Returns the cosine of the angle between this vector and another,
+    /// i.e. their dot product over the product of their norms, which is
+    /// 1 for vectors in the same direction, and -1 for opposite ones.
+    /// All three products are taken by [`Vector::dot`], so on AMX where
+    /// it is available. The zero vector has no direction, so if either
+    /// one is zero (or has no data), this returns 0, rather than NaN.
+    /// The vectors must be the same length, or this will panic.
+    pub fn cosine_similarity(&self, other: &Vector<T>) -> T {
+        let dot = self.dot(other);
+        let (a, b) = (self.dot(self).sqrt(), other.dot(other).sqrt());
+        if a == T::default() || b == T::default() {
+            return T::default();
+        }
+
+        dot / (a * b)
+    }
+}
+
+impl<T: ops::Mul<Output = T> + Copy> Vector<T> {
+    /// Returns the outer product of this vector and the other, as
+    /// [`Vector::outer`] does, but with plain scalar code and nothing
+    /// else, so that it works for any scalar which can be multiplied.
+    pub fn outer_naive(&self, other: &Vector<T>) -> Matrix<T> {
+        let (m, n) = (self.0.vlen(), other.0.vlen());
+        let data = match (self.0.data(), other.0.data()) {
+            (Some(a), Some(b)) => Some(
+                b.iter()
+                    .flat_map(|&y| a.iter().map(move |&x| x * y))
+                    .collect(),
+            ),
+            _ => None,
+        };
+
+        Matrix(Tensor { data, dims: shape(&[m, n]) })
+    }
+}
+
+/// Returns the dot product of `a` and `b`, which must be of the same
+/// length, on AMX. Each op adds the outer product of one register of
+/// X and the same one of Y to Z, the diagonal of which is their
+/// elementwise product, so the dot product is the sum of Z's diagonal.
+fn dot_amx<T: AmxScalar>(amx: &AmxCtx, a: &[T], b: &[T]) -> T {
+    if a.is_empty() {
+        return T::default();
+    }
+
+    let (mut x, mut y) = ([0u8; 512], [0u8; 512]);
+    let size = mem::size_of::<T>();
+    // Every op below adds onto Z, so start it from zero, rather than
+    // from whatever the last op in this thread left there.
+    amx.clear_z();
+
+    // Each chunk fills all DEPTH registers, row by row. Since a row is
+    // exactly TILE scalars, element `i` of a chunk is at byte `i * size`.
+    for (ca, cb) in a
+        .chunks(T::TILE * DEPTH)
+        .zip(b.chunks(T::TILE * DEPTH))
+    {
+        x.fill(0);
+        y.fill(0);
+        ca.iter()
+            .zip(cb)
+            .enumerate()
+            .for_each(|(i, (&p, &q))| {
+                T::put(&mut x, i * size, p);
+                T::put(&mut y, i * size, q);
+            });
+
+        amx.set_matrix(RegSet::X, &x);
+        amx.set_matrix(RegSet::Y, &y);
+        // One op per register the chunk fills, since each reads just one.
+        (0..ca.len().div_ceil(T::TILE)).for_each(|r| T::matrix_mul_add(amx, r as u8));
+    }
+
+    // See `multiply_blocked` for the layout of the product in Z.
+    let z = amx.get_matrix_4096();
+    let stride = 64 / T::TILE * 64;
+    (0..T::TILE).fold(T::default(), |acc, i| acc + T::get(&z, i * stride + i * size))
+}
@@ -0,0 +1,190 @@
+//! Einstein summation over tensors, as NumPy's `einsum`, though only
+//! with an explicit output, e.g. `ij,jk->ik`. Any spec of that form is
+//! computed, however naively, bar the matrix multiply, which is sent
+//! to [`Matrix::multiply`](crate::Matrix::multiply)'s backends.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::matmul::multiply_slices;
+use crate::arch::amx::AmxScalar;
+use crate::space::{strides, NzU16, Tensor, UNUSED};
+
+/// An error returned by [`Tensor::einsum`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum EinsumErr {
+    /// The spec isn't of the form `ab,bc->ac`, i.e. comma-separated
+    /// subscripts, then `->`, then the output's, each a letter per dim.
+    Parse,
+    /// The spec has subscripts for `expected` operands, but `got` were
+    /// given.
+    OperandCount { expected: usize, got: usize },
+    /// Operand `index` has fewer subscripts than its rank, or more than
+    /// 8, so some of its dims would go without one.
+    RankMismatch { index: usize },
+    /// Subscript `label` is given to dims of different lengths, so they
+    /// can't be summed over together.
+    DimMismatch { label: char },
+    /// Subscript `label` is in the output, but in no operand, or is in
+    /// the output more than once.
+    BadOutput { label: char },
+}
+
+impl<T: AmxScalar> Tensor<T> {
+    /// Returns the Einstein summation of `operands` given by `spec`, as
+    /// NumPy's `einsum` does, e.g. `ij,jk->ik` for a matrix multiply,
+    /// `ij->ji` for a transpose, or `ii->` for a trace. Each operand is
+    /// given a letter per dim, and the output is the sum of products of
+    /// the operands, over every letter not in the output. The output
+    /// must be given explicitly, after `->`. If any operand has no data,
+    /// neither does the result.
+    pub fn einsum(spec: &str, operands: &[&Tensor<T>]) -> Result<Tensor<T>, EinsumErr> {
+        let spec: Vec<char> = spec
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        let arrow = spec
+            .windows(2)
+            .position(|w| w == ['-', '>'])
+            .ok_or(EinsumErr::Parse)?;
+        let (inputs, output) = (&spec[..arrow], &spec[arrow + 2..]);
+        let inputs: Vec<&[char]> = inputs.split(|&c| c == ',').collect();
+        if !inputs
+            .iter()
+            .chain([&output])
+            .flat_map(|s| s.iter())
+            .all(|c| c.is_alphabetic())
+        {
+            return Err(EinsumErr::Parse);
+        }
+        if inputs.len() != operands.len() {
+            return Err(EinsumErr::OperandCount { expected: inputs.len(), got: operands.len() });
+        }
+
+        // Each letter, in order of first appearance, and its length,
+        // which every dim it's given to must have.
+        let mut labels: Vec<(char, usize)> = Vec::new();
+        for (index, (subs, t)) in inputs.iter().zip(operands).enumerate() {
+            if subs.len() < t.rank() || subs.len() > 8 {
+                return Err(EinsumErr::RankMismatch { index });
+            }
+            for (&label, d) in subs.iter().zip(t.dims()) {
+                match labels.iter().find(|(l, _)| *l == label) {
+                    Some(&(_, len)) if len != d.get() as usize => {
+                        return Err(EinsumErr::DimMismatch { label })
+                    }
+                    Some(_) => {}
+                    None => labels.push((label, d.get() as usize)),
+                }
+            }
+        }
+
+        let mut dims = [UNUSED; 8];
+        for (k, &label) in output.iter().enumerate() {
+            let len = labels
+                .iter()
+                .find(|(l, _)| *l == label)
+                .map(|&(_, len)| len);
+            match len {
+                Some(len) if k < 8 && !output[..k].contains(&label) => {
+                    // Safe to unwrap: It's the length of an existing dim.
+                    dims[k] = NzU16::new(len as u16).unwrap();
+                }
+                _ => return Err(EinsumErr::BadOutput { label }),
+            }
+        }
+
+        let Some(data) = operands
+            .iter()
+            .map(|t| t.data())
+            .collect::<Option<Vec<_>>>()
+        else {
+            return Ok(Tensor { data: None, dims });
+        };
+
+        // `ij,jk->ik` is a matrix multiply, if `i`, `j` and `k` differ.
+        if let ([[i, j], [j2, k]], [i2, k2]) = (&inputs[..], output) {
+            if j == j2 && i == i2 && k == k2 && i != j && j != k && i != k {
+                let (m, k, n) = (
+                    dims[0].get() as usize,
+                    operands[0].dims()[1].get() as usize,
+                    dims[1].get() as usize,
+                );
+                let data = multiply_slices(data[0], data[1], m, k, n);
+                return Ok(Tensor { data: Some(data), dims });
+            }
+        }
+
+        Ok(Tensor {
+            data: Some(contract(&inputs, output, &labels, operands, &data, &dims)),
+            dims,
+        })
+    }
+}
+
+/// Compute an Einstein summation naively, i.e. walk every combination
+/// of values of every label, and add the product of the operands at
+/// each one to the output at its coordinates. `labels` holds each label
+/// and its length, and `dims` the output's, both already checked.
+fn contract<T: AmxScalar>(
+    inputs: &[&[char]],
+    output: &[char],
+    labels: &[(char, usize)],
+    operands: &[&Tensor<T>],
+    data: &[&[T]],
+    dims: &[NzU16; 8],
+) -> Vec<T> {
+    // The stride of each label in each operand, and in the output, i.e.
+    // how far its offset moves when that label's value goes up by 1. A
+    // label given to two dims of one operand, e.g. `ii`, moves by both.
+    let stride_of = |subs: &[char], strides: [usize; 8]| -> Vec<usize> {
+        labels
+            .iter()
+            .map(|&(l, _)| {
+                (0..subs.len())
+                    .filter(|&d| subs[d] == l)
+                    .map(|d| strides[d])
+                    .sum()
+            })
+            .collect()
+    };
+    let ins: Vec<Vec<usize>> = inputs
+        .iter()
+        .zip(operands)
+        .map(|(subs, t)| stride_of(subs, strides(&t.dims())))
+        .collect();
+    let out = stride_of(output, strides(dims));
+
+    let mut result = vec![T::default(); dims.iter().map(|d| d.get() as usize).product()];
+    let total: usize = labels.iter().map(|&(_, len)| len).product();
+    let mut values = vec![0; labels.len()];
+    for i in 0..total {
+        // Decode `i` into a value per label, the first varying fastest.
+        let mut rest = i;
+        labels
+            .iter()
+            .enumerate()
+            .for_each(|(l, &(_, len))| {
+                values[l] = rest % len;
+                rest /= len;
+            });
+        let at = |strides: &[usize]| {
+            (0..labels.len())
+                .map(|l| values[l] * strides[l])
+                .sum::<usize>()
+        };
+
+        let product = ins
+            .iter()
+            .zip(data)
+            .map(|(strides, d)| d[at(strides)])
+            .reduce(|acc, x| acc * x);
+        if let Some(p) = product {
+            let j = at(&out);
+            result[j] = result[j] + p;
+        }
+    }
+
+    result
+}
@@ -0,0 +1,162 @@
+//! Determinants and inverses of square matrices. Small ones, up to
+//! 4x4, e.g. for graphics transforms, use cofactor expansion, which is
+//! exact up to rounding. Larger ones use LU decomposition instead.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::lu;
+use crate::invar::Float;
+use crate::space::{Matrix, Tensor};
+
+/// The largest matrix which is inverted by cofactor expansion. Past
+/// this, its `n!` cost loses out to the `n^3` of LU decomposition.
+const COFACTOR_MAX: usize = 4;
+
+impl<T: Float> Matrix<T> {
+    /// Returns the determinant of this matrix, or `None` if it isn't
+    /// square, or has no data.
+    pub fn det(&self) -> Option<T> {
+        let (n, a) = (self.0.vlen(), self.0.data()?);
+        if !self.is_square() {
+            return None;
+        }
+
+        Some(if n <= COFACTOR_MAX {
+            det_cofactor(a, n)
+        } else {
+            det_lu(a, n)
+        })
+    }
+
+    /// Returns the inverse of this matrix, or `None` if it isn't square,
+    /// has no data, or is singular, so that it has no (stable) inverse.
+    /// It's singular if some pivot of its LU decomposition is at most
+    /// `n * EPSILON` times its largest column, by absolute sum, i.e. the
+    /// rounding error elimination can leave there. This scales with the
+    /// matrix, so e.g. `0.01 * I` is never singular, and is the same for
+    /// every `n`, even those inverted by cofactor expansion.
+    pub fn inverse(&self) -> Option<Matrix<T>> {
+        let (n, a) = (self.0.vlen(), self.0.data()?);
+        if !self.is_square() {
+            return None;
+        }
+
+        let d = lu::decompose(a, n, pivot_tol(a, n))?;
+        // The determinant can underflow to zero where the pivots don't,
+        // e.g. for `1e-200 * I`, so fall back on LU in that case.
+        let inv = if n <= COFACTOR_MAX {
+            inverse_cofactor(a, n).unwrap_or_else(|| inverse_lu(&d, n))
+        } else {
+            inverse_lu(&d, n)
+        };
+        Some(Matrix(Tensor { data: Some(inv), dims: self.0.dims() }))
+    }
+}
+
+/// The tolerance within which a pivot of the (column-major) NxN matrix
+/// `a` counts as zero, when inverting it: `n` epsilons of its largest
+/// column, by absolute sum. Being relative to `a`, scaling `a` scales
+/// this alike, so doesn't change whether it's singular.
+fn pivot_tol<T: Float>(a: &[T], n: usize) -> T {
+    let norm = a
+        .chunks(n)
+        .map(|c| {
+            c.iter()
+                .fold(T::default(), |acc, &x| acc + x.abs())
+        })
+        .fold(T::default(), |max, s| if s > max { s } else { max });
+    T::from_usize(n) * T::EPSILON * norm
+}
+
+/// The (column-major) NxN matrix `a`, less row `r` and column `c`.
+fn minor<T: Float>(a: &[T], n: usize, r: usize, c: usize) -> Vec<T> {
+    (0..n)
+        .filter(|&j| j != c)
+        .flat_map(|j| {
+            (0..n)
+                .filter(move |&i| i != r)
+                .map(move |i| a[j * n + i])
+        })
+        .collect()
+}
+
+/// The determinant of the (column-major) NxN matrix `a`, expanding
+/// along its first row into the cofactors of each of its elements.
+fn det_cofactor<T: Float>(a: &[T], n: usize) -> T {
+    match n {
+        1 => a[0],
+        2 => a[0] * a[3] - a[2] * a[1],
+        _ => (0..n).fold(T::default(), |acc, j| {
+            let term = a[j * n] * det_cofactor(&minor(a, n, 0, j), n - 1);
+            if j % 2 == 0 {
+                acc + term
+            } else {
+                acc - term
+            }
+        }),
+    }
+}
+
+/// The inverse of the (column-major) NxN matrix `a`, i.e. its adjugate
+/// (the transpose of its cofactors) divided by its determinant. This
+/// only fails if that is exactly zero: the caller tests for singularity.
+fn inverse_cofactor<T: Float>(a: &[T], n: usize) -> Option<Vec<T>> {
+    let det = det_cofactor(a, n);
+    if det == T::default() {
+        return None;
+    }
+    if n == 1 {
+        return Some(vec![T::from_usize(1) / det]);
+    }
+
+    // Element (i, j) of the inverse is cofactor (j, i) over the det.
+    Some(
+        (0..n)
+            .flat_map(|j| (0..n).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let c = det_cofactor(&minor(a, n, j, i), n - 1) / det;
+                if (i + j) % 2 == 0 {
+                    c
+                } else {
+                    T::default() - c
+                }
+            })
+            .collect(),
+    )
+}
+
+/// The determinant of the (column-major) NxN matrix `a`, by way of its
+/// LU decomposition: the product of the diagonal of `U`, negated once
+/// for each swap of rows on the way. This only gives up on an exactly
+/// zero pivot, when `a` is singular, and so has determinant 0: a small
+/// pivot just makes for a small determinant.
+fn det_lu<T: Float>(a: &[T], n: usize) -> T {
+    match lu::decompose(a, n, T::default()) {
+        Some(d) => {
+            let det = (0..n).fold(T::from_usize(1), |acc, i| acc * d.lu[i * n + i]);
+            if d.swaps % 2 == 0 {
+                det
+            } else {
+                T::default() - det
+            }
+        }
+        None => T::default(),
+    }
+}
+
+/// The inverse of the NxN matrix `A`, by way of its LU decomposition
+/// `d`: column `j` of `A^-1` solves `Ax = e_j`, where `e_j` is column
+/// `j` of the identity. So this decomposes once, solves N times.
+fn inverse_lu<T: Float>(d: &lu::Lu<T>, n: usize) -> Vec<T> {
+    let mut e = vec![T::default(); n];
+
+    (0..n)
+        .flat_map(|j| {
+            e[j] = T::from_usize(1);
+            let col = lu::solve(d, n, &e);
+            e[j] = T::default();
+            col
+        })
+        .collect()
+}
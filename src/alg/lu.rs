@@ -0,0 +1,115 @@
+//! LU decomposition of square matrices, with partial pivoting, and the
+//! linear solves it makes cheap. This also backs [`Matrix::inverse`] &
+//! [`Matrix::det`] for matrices too large for cofactor expansion.
+
+use alloc::vec::Vec;
+
+use crate::invar::Float;
+use crate::space::{shape, Matrix, Tensor, Vector};
+
+/// The LU decomposition of an NxN matrix `A`. `lu` holds both factors
+/// in one (column-major) NxN matrix: `U` on and above the diagonal, &
+/// `L` below it, less its diagonal, which is all 1s. Row `i` of `LU` is
+/// row `perm[i]` of `A`, which took `swaps` swaps of rows to arrive at.
+pub(crate) struct Lu<T> {
+    pub(crate) lu: Vec<T>,
+    pub(crate) perm: Vec<usize>,
+    pub(crate) swaps: usize,
+}
+
+/// Decompose the (column-major) NxN matrix `a` by Gaussian elimination,
+/// with partial pivoting, i.e. at each step swapping up the row with
+/// the largest pivot, for stability. Returns `None` if `a` is singular,
+/// i.e. some pivot is within `tol` of zero, e.g. [`Float::EPSILON`].
+pub(crate) fn decompose<T: Float>(a: &[T], n: usize, tol: T) -> Option<Lu<T>> {
+    let (mut lu, mut perm, mut swaps) = (a.to_vec(), (0..n).collect::<Vec<_>>(), 0);
+
+    for k in 0..n {
+        let p = (k..n).fold(k, |p, i| {
+            if lu[k * n + i].abs() > lu[k * n + p].abs() {
+                i
+            } else {
+                p
+            }
+        });
+        if lu[k * n + p].abs() <= tol {
+            return None;
+        }
+        if p != k {
+            (0..n).for_each(|j| lu.swap(j * n + k, j * n + p));
+            perm.swap(k, p);
+            swaps += 1;
+        }
+
+        // Eliminate below the pivot, keeping each multiplier in place of
+        // the element it eliminated, which is where `L` keeps it.
+        for i in k + 1..n {
+            let f = lu[k * n + i] / lu[k * n + k];
+            lu[k * n + i] = f;
+            (k + 1..n).for_each(|j| lu[j * n + i] = lu[j * n + i] - f * lu[j * n + k]);
+        }
+    }
+
+    Some(Lu { lu, perm, swaps })
+}
+
+/// Solve `Ax = b` for `x`, given the decomposition of `A`, by forward
+/// substitution through `L`, then back substitution through `U`.
+pub(crate) fn solve<T: Float>(lu: &Lu<T>, n: usize, b: &[T]) -> Vec<T> {
+    let Lu { lu, perm, .. } = lu;
+    let mut x: Vec<T> = perm.iter().map(|&p| b[p]).collect();
+
+    for i in 0..n {
+        x[i] = (0..i).fold(x[i], |acc, j| acc - lu[j * n + i] * x[j]);
+    }
+    for i in (0..n).rev() {
+        x[i] = (i + 1..n).fold(x[i], |acc, j| acc - lu[j * n + i] * x[j]) / lu[i * n + i];
+    }
+
+    x
+}
+
+impl<T: Float> Matrix<T> {
+    /// Returns the LU decomposition of this matrix, i.e. `L`, which is
+    /// lower triangular with 1s on its diagonal, `U`, which is upper
+    /// triangular, and a permutation of the rows of this matrix, such
+    /// that row `i` of `LU` is row `perm[i]` of this matrix. In other
+    /// words, `A = PLU`, where row `perm[i]` of `P` is 1 in column `i`.
+    /// Returns `None` if this matrix isn't square, has no data, or is
+    /// singular, i.e. some pivot is within [`Float::EPSILON`] of zero.
+    pub fn lu(&self) -> Option<(Matrix<T>, Matrix<T>, Vec<usize>)> {
+        let (n, a) = (self.0.vlen(), self.0.data()?);
+        if !self.is_square() {
+            return None;
+        }
+
+        let Lu { lu, perm, .. } = decompose(a, n, T::EPSILON)?;
+        let (one, zero) = (T::from_usize(1), T::default());
+        let l = (0..n * n).map(|e| match (e % n, e / n) {
+            (i, j) if i > j => lu[e],
+            (i, j) if i == j => one,
+            _ => zero,
+        });
+        let u = (0..n * n).map(|e| if e % n <= e / n { lu[e] } else { zero });
+
+        Some((
+            Matrix(Tensor { data: Some(l.collect()), dims: self.0.dims() }),
+            Matrix(Tensor { data: Some(u.collect()), dims: self.0.dims() }),
+            perm,
+        ))
+    }
+
+    /// Solve `Ax = b` for `x`, where `A` is this matrix, by way of its
+    /// [`Matrix::lu`] decomposition. Returns `None` in the same cases
+    /// as that does, or if `b` has no data, or isn't as long as `A` is
+    /// tall. To solve for many `b`, it's cheaper to invert `A` once.
+    pub fn solve(&self, b: &Vector<T>) -> Option<Vector<T>> {
+        let (n, a, b) = (self.0.vlen(), self.0.data()?, b.0.data()?);
+        if !self.is_square() || b.len() != n {
+            return None;
+        }
+
+        let x = solve(&decompose(a, n, T::EPSILON)?, n, b);
+        Some(Vector(Tensor { data: Some(x), dims: shape(&[n]) }))
+    }
+}
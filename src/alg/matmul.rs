@@ -0,0 +1,348 @@
+//! Matrix multiplication. This is dispatched to the fastest backend
+//! available on the target: AMX first, then NEON or AVX2, and only
+//! then to plain scalar code.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+
+use crate::arch::amx::{with_amx, AmxCtx, AmxOps, AmxScalar, RegSet, DEPTH};
+use crate::arch::{scalar, Backend};
+use crate::invar::{One, Scalar, Zero};
+use crate::space::{shape, Matrix, Tensor, Vector};
+
+/// An error returned by [`Matrix::multiply`] and [`Matrix::multiply_add_into`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum MulErr {
+    /// The width of the LHS is not the same as the height of the RHS,
+    /// so the two matrices cannot be multiplied.
+    DimMismatch { lhs_w: usize, rhs_h: usize },
+    /// The accumulator isn't the same size as the product, i.e. it's
+    /// not HxW, given an HxK LHS and a KxW RHS. Both are `(h, w)`.
+    AccMismatch { expected: (usize, usize), got: (usize, usize) },
+}
+
+/// An error returned by [`Matrix::multiply_with`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum BackendErr {
+    /// The backend isn't available, either on this target, or for this
+    /// scalar type, e.g. NEON for `f32`.
+    Unavailable(Backend),
+    /// The matrices couldn't be multiplied on any backend.
+    Mul(MulErr),
+}
+
+impl From<MulErr> for BackendErr {
+    fn from(e: MulErr) -> Self {
+        BackendErr::Mul(e)
+    }
+}
+
+impl<T: AmxScalar> Matrix<T> {
+    /// Multiply this matrix by another matrix, returning the product.
+    /// An HxK matrix may only be multiplied by a KxW matrix, and the
+    /// product is HxW. The operands are split into blocks that fit in
+    /// the AMX registers, so there is no limit on their size besides
+    /// that of the dims, and are multiplied on the coprocessor where
+    /// possible. In 32-bit mode only 16x16 blocks fit, so `f32` takes
+    /// twice as many passes over each dimension as `f16`.
+    pub fn multiply(self, rhs: Matrix<T>) -> Result<Matrix<T>, MulErr> {
+        multiply(self, rhs)
+    }
+
+    /// Multiply this matrix by another matrix, as [`Matrix::multiply`]
+    /// does, but on the given `backend`, rather than the fastest one.
+    /// This is for benchmarking, and checking backends against each
+    /// other, so it's an error if `backend` isn't available, rather
+    /// than silently falling back to another one. (Unless either one
+    /// has no data, in which case there's nothing to multiply at all.)
+    pub fn multiply_with(&self, rhs: &Matrix<T>, backend: Backend) -> Result<Matrix<T>, BackendErr> {
+        let (m, k, n) = (self.0.vlen(), self.0.hlen(), rhs.0.hlen());
+        if k != rhs.0.vlen() {
+            return Err(MulErr::DimMismatch { lhs_w: k, rhs_h: rhs.0.vlen() }.into());
+        }
+
+        let data = match (self.0.data(), rhs.0.data()) {
+            (Some(a), Some(b)) => Some(multiply_slices_with(backend, a, b, m, k, n)?),
+            _ => None,
+        };
+
+        Ok(Matrix(Tensor { data, dims: shape(&[m, n]) }))
+    }
+
+    /// Multiply this matrix by another matrix, adding the product to
+    /// `acc`, i.e. `acc += self * rhs`, the fused op at the heart of any
+    /// blocked matrix multiply. On AMX, each block of `acc` is loaded
+    /// into Z, and every product is accumulated onto it there, so the
+    /// sum costs nothing extra. Otherwise, the fallback kernel adds the
+    /// product to `acc` as it goes, without allocating. `acc` must be HxW, given
+    /// an HxK `self` and a KxW `rhs`. If any of the three has no data,
+    /// `acc` is left as it is.
+    pub fn multiply_add_into(&self, rhs: &Matrix<T>, acc: &mut Matrix<T>) -> Result<(), MulErr> {
+        let (m, k, n) = (self.0.vlen(), self.0.hlen(), rhs.0.hlen());
+        if k != rhs.0.vlen() {
+            return Err(MulErr::DimMismatch { lhs_w: k, rhs_h: rhs.0.vlen() });
+        }
+        if (acc.0.vlen(), acc.0.hlen()) != (m, n) || acc.0.rank() > 2 {
+            return Err(MulErr::AccMismatch { expected: (m, n), got: (acc.0.vlen(), acc.0.hlen()) });
+        }
+
+        if let (Some(a), Some(b), Some(c)) = (self.0.data(), rhs.0.data(), acc.0.data.as_mut()) {
+            if with_amx(|amx| multiply_blocked::<T, true>(amx, a, b, c, m, k, n)).is_err() {
+                T::matrix_mul_add_fallback(a, b, c, m, k, n);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Multiply this matrix by a vector, returning the product. The
+    /// vector is treated as a Wx1 matrix, so an HxW matrix may only be
+    /// multiplied by a vector of length W, and the product has length
+    /// H. Otherwise, this works exactly as [`Matrix::multiply`] does.
+    pub fn multiply_vec(&self, v: &Vector<T>) -> Vector<T> {
+        let (m, k) = (self.0.vlen(), self.0.hlen());
+        assert_eq!(
+            k,
+            v.0.vlen(),
+            "cannot multiply a matrix of width {} by a vector of length {}",
+            k,
+            v.0.vlen()
+        );
+
+        let data = match (self.0.data(), v.0.data()) {
+            (Some(a), Some(b)) => Some(multiply_slices(a, b, m, k, 1)),
+            _ => None,
+        };
+
+        Vector(Tensor { data, dims: shape(&[m]) })
+    }
+}
+
+impl<T: AmxScalar> core::ops::Mul for Matrix<T> {
+    type Output = Self;
+
+    /// The matrix product, as [`Matrix::multiply`] returns, *not* the
+    /// elementwise product that `*` on tensors returns. This panics if
+    /// the width of the LHS isn't the height of the RHS: if you can't
+    /// rule that out, call [`Matrix::multiply`] for the error instead.
+    fn mul(self, rhs: Self) -> Self::Output {
+        match multiply(self, rhs) {
+            Ok(product) => product,
+            Err(MulErr::DimMismatch { lhs_w, rhs_h }) => {
+                panic!("cannot multiply a matrix of width {} by one of height {}", lhs_w, rhs_h)
+            }
+            Err(e) => panic!("cannot multiply matrices: {:?}", e),
+        }
+    }
+}
+
+impl<T: AmxScalar> core::ops::Mul<&Vector<T>> for &Matrix<T> {
+    type Output = Vector<T>;
+
+    /// The matrix-vector product, as [`Matrix::multiply_vec`] returns,
+    /// which panics if the width of the matrix isn't the vector's length.
+    fn mul(self, rhs: &Vector<T>) -> Self::Output {
+        self.multiply_vec(rhs)
+    }
+}
+
+impl<T: AmxScalar + Zero + One> Matrix<T> {
+    /// Raise this matrix to the power `n`, i.e. the product of `n` copies
+    /// of it, e.g. to step a Markov chain `n` times at once. This is
+    /// done by repeated squaring, so it takes O(log n) multiplies, each
+    /// by [`Matrix::multiply`], and `pow(0)` is [`Matrix::identity`].
+    /// This panics if the matrix isn't square. If it has no data, then
+    /// neither does the result.
+    pub fn pow(&self, n: u32) -> Matrix<T> {
+        assert!(
+            self.is_square(),
+            "cannot raise a {}x{} matrix to a power, as it isn't square",
+            self.0.vlen(),
+            self.0.hlen()
+        );
+        if self.0.data().is_none() {
+            return self.clone();
+        }
+
+        let (mut base, mut n) = (self.clone(), n);
+        let mut result: Option<Matrix<T>> = None;
+        while n > 0 {
+            if n & 1 == 1 {
+                result = Some(match result {
+                    Some(r) => r * base.clone(),
+                    None => base.clone(),
+                });
+            }
+            n >>= 1;
+            if n > 0 {
+                base = base.clone() * base;
+            }
+        }
+
+        result.unwrap_or_else(|| Matrix::identity(self.0.vlen()))
+    }
+}
+
+impl Matrix<i16> {
+    /// Multiply this matrix by another matrix, returning the product as
+    /// `i32`s, i.e. widening each element before it's multiplied, so
+    /// sums are exact where [`Matrix::multiply`] would overflow. AMX only
+    /// accumulates `i16` into `i16`, so this is computed as
+    /// [`Matrix::multiply_naive`] does, and panics as it does. Sums can
+    /// still overflow `i32`, but only past 2^31, e.g. 2 products of
+    /// `i16::MIN`s, or 2^17 of 8-bit values.
+    pub fn multiply_widening(&self, rhs: &Matrix<i16>) -> Matrix<i32> {
+        Matrix(self.0.cast()).multiply_naive(&Matrix(rhs.0.cast()))
+    }
+}
+
+impl<T: Scalar + Zero> Matrix<T> {
+    /// Multiply this matrix by another matrix, returning the product,
+    /// as [`Matrix::multiply`] does, but with the textbook triple loop
+    /// and nothing else. This is slow, but it's the reference that the
+    /// other backends are checked against, and works for any scalar.
+    /// This panics if the width of `self` isn't the height of `rhs`.
+    pub fn multiply_naive(&self, rhs: &Matrix<T>) -> Matrix<T> {
+        let (m, k, n) = (self.0.vlen(), self.0.hlen(), rhs.0.hlen());
+        assert_eq!(
+            k,
+            rhs.0.vlen(),
+            "cannot multiply a matrix of width {} by one of height {}",
+            k,
+            rhs.0.vlen()
+        );
+
+        let data = match (self.0.data(), rhs.0.data()) {
+            (Some(a), Some(b)) => {
+                let mut c = vec![T::zero(); m * n];
+                scalar::matrix_mul_add(a, b, &mut c, m, k, n);
+                Some(c)
+            }
+            _ => None,
+        };
+
+        Matrix(Tensor { data, dims: shape(&[m, n]) })
+    }
+}
+
+/// Multiply `lhs` by `rhs` on the AMX coprocessor, or if it isn't
+/// available, by calling [`AmxScalar::matrix_mul_fallback`] instead.
+fn multiply<T: AmxScalar>(lhs: Matrix<T>, rhs: Matrix<T>) -> Result<Matrix<T>, MulErr> {
+    let (m, k, n) = (lhs.0.vlen(), lhs.0.hlen(), rhs.0.hlen());
+    if k != rhs.0.vlen() {
+        return Err(MulErr::DimMismatch { lhs_w: k, rhs_h: rhs.0.vlen() });
+    }
+
+    let (a, b) = match (lhs.0.data(), rhs.0.data()) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Ok(Matrix(Tensor { data: None, dims: shape(&[m, n]) })),
+    };
+
+    Ok(Matrix(Tensor {
+        data: Some(multiply_slices(a, b, m, k, n)),
+        dims: shape(&[m, n]),
+    }))
+}
+
+/// Multiply `a` (MxK) by `b` (KxN), both column-major, returning the
+/// MxN product. This is where we pick between AMX and the fallback.
+pub(super) fn multiply_slices<T: AmxScalar>(a: &[T], b: &[T], m: usize, k: usize, n: usize) -> Vec<T> {
+    let mut c = vec![T::default(); m * n];
+    if with_amx(|amx| multiply_blocked::<T, false>(amx, a, b, &mut c, m, k, n)).is_err() {
+        T::matrix_mul_fallback(a, b, &mut c, m, k, n)
+    }
+    c
+}
+
+/// Multiply `a` (MxK) by `b` (KxN), both column-major, returning the
+/// MxN product, on `backend` only, or an error if it's unavailable.
+fn multiply_slices_with<T: AmxScalar>(
+    backend: Backend,
+    a: &[T],
+    b: &[T],
+    m: usize,
+    k: usize,
+    n: usize,
+) -> Result<Vec<T>, BackendErr> {
+    let mut c = vec![T::default(); m * n];
+    match backend {
+        Backend::Amx => with_amx(|amx| multiply_blocked::<T, false>(amx, a, b, &mut c, m, k, n))
+            .map_err(|_| BackendErr::Unavailable(backend))?,
+        _ => T::kernel(backend).ok_or(BackendErr::Unavailable(backend))?(a, b, &mut c, m, k, n),
+    }
+    Ok(c)
+}
+
+/// Multiply `a` (MxK) by `b` (KxN) into `c` (MxN), all column-major,
+/// one TILExTILE block of `c` at a time. Each block is the sum of the
+/// products of TILExDEPTH blocks of `a` and DEPTHxTILE blocks of `b`,
+/// which is accumulated in Z, so it's only read back once per block.
+/// If `ACC`, that sum starts from the block of `c`, rather than zero,
+/// so the product is added to `c`, rather than overwriting it.
+fn multiply_blocked<T: AmxScalar, const ACC: bool>(
+    amx: &AmxCtx,
+    a: &[T],
+    b: &[T],
+    c: &mut [T],
+    m: usize,
+    k: usize,
+    n: usize,
+) {
+    let (mut x, mut y) = ([0u8; 512], [0u8; 512]);
+    let size = mem::size_of::<T>();
+    // Products are spread evenly over the 64 rows of Z, i.e. 2 rows
+    // apart for halfs, 4 for floats, so column `j` of the block is
+    // found in Z row `j * 64 / TILE`.
+    let stride = 64 / T::TILE * 64;
+
+    for i0 in (0..m).step_by(T::TILE) {
+        for j0 in (0..n).step_by(T::TILE) {
+            let (th, tw) = (T::TILE.min(m - i0), T::TILE.min(n - j0));
+
+            if ACC {
+                // Lay the block of `c` out in Z as the product would be,
+                // so that every product below accumulates onto it.
+                let mut z = [0u8; 4096];
+                (0..tw).for_each(|j| {
+                    (0..th).for_each(|i| T::put(&mut z, j * stride + i * size, c[(j0 + j) * m + i0 + i]));
+                });
+                amx.set_matrix(RegSet::Z, &z);
+            }
+
+            for p0 in (0..k).step_by(DEPTH) {
+                let depth = DEPTH.min(k - p0);
+                // Zero the registers first, so that the parts of each
+                // block which overhang the matrices contribute nothing.
+                x.fill(0);
+                y.fill(0);
+                (0..depth).for_each(|q| {
+                    // X row `q` is (part of) column `p0 + q` of `a`, and Y
+                    // row `q` is (part of) row `p0 + q` of `b`.
+                    (0..th).for_each(|i| T::put(&mut x, q * 64 + i * size, a[(p0 + q) * m + i0 + i]));
+                    (0..tw).for_each(|j| T::put(&mut y, q * 64 + j * size, b[(j0 + j) * k + p0 + q]));
+                });
+
+                amx.set_matrix(RegSet::X, &x);
+                amx.set_matrix(RegSet::Y, &y);
+                // Each op multiplies one pair of rows, so this takes one
+                // per row. The first product overwrites whatever the last
+                // block left in Z. Every one after that accumulates onto it.
+                (0..depth).for_each(|q| {
+                    if p0 == 0 && q == 0 && !ACC {
+                        T::matrix_mul(amx, q as u8);
+                    } else {
+                        T::matrix_mul_add(amx, q as u8);
+                    }
+                });
+            }
+
+            let z = amx.get_matrix_4096();
+            (0..tw).for_each(|j| {
+                (0..th).for_each(|i| c[(j0 + j) * m + i0 + i] = T::get(&z, j * stride + i * size));
+            });
+        }
+    }
+}
@@ -3,5 +3,15 @@
 //! operations.
 
 mod arith;
+mod dot;
+mod einsum;
+mod inverse;
+mod lu;
+mod matmul;
+mod norm;
+mod quant;
+mod reduce;
+mod softmax;
 
-pub use arith::*;
+pub use einsum::EinsumErr;
+pub use matmul::*;
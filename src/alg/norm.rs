@@ -0,0 +1,93 @@
+//! Norms of vectors, i.e. their lengths, and normalisation, i.e.
+//! scaling them to unit length.
+
+use core::cmp::Ordering;
+
+use crate::invar::Float;
+use crate::space::Vector;
+
+impl<T: Float> Vector<T> {
+    /// Returns the L2 (Euclidean) norm of this vector, i.e. the square
+    /// root of its dot product with itself. A vector with no data has
+    /// a norm of zero.
+    pub fn norm(&self) -> T {
+        self.0
+            .data()
+            .unwrap_or(&[])
+            .iter()
+            .fold(T::default(), |acc, &x| acc + x * x)
+            .sqrt()
+    }
+
+    /// Returns the Lp norm of this vector, i.e. `(sum |x|^p)^(1/p)`. The
+    /// common cases are specialised: `p == 1` is the sum of the absolute
+    /// values, and `p == 2` is [`Vector::norm`]. By convention, `p == 0`
+    /// is the number of nonzero elements, though it's not a true norm.
+    pub fn norm_p(&self, p: u32) -> T {
+        let d = self.0.data().unwrap_or(&[]);
+        match p {
+            0 => T::from_usize(d.iter().filter(|&&x| x != T::default()).count()),
+            1 => d
+                .iter()
+                .fold(T::default(), |acc, &x| acc + x.abs()),
+            2 => self.norm(),
+            _ => root(
+                d.iter()
+                    .fold(T::default(), |acc, &x| acc + pow(x.abs(), p)),
+                p,
+            ),
+        }
+    }
+
+    /// Returns the L∞ norm of this vector, i.e. the largest absolute
+    /// value of any element, which is the limit of the Lp norm as `p`
+    /// grows. A vector with no data has a norm of zero.
+    pub fn norm_inf(&self) -> T {
+        self.0
+            .data()
+            .unwrap_or(&[])
+            .iter()
+            .map(|&x| x.abs())
+            .fold(T::default(), |max, x| if x > max { x } else { max })
+    }
+
+    /// Scale this vector to unit length, by dividing each element by
+    /// its [`Vector::norm`]. The zero vector has no direction, so it
+    /// is returned as it is, rather than divided by zero into NaNs.
+    pub fn normalize(mut self) -> Vector<T> {
+        let norm = self.norm();
+        if norm == T::default() {
+            return self;
+        }
+
+        if let Some(d) = &mut self.0.data {
+            d.iter_mut().for_each(|x| *x = *x / norm);
+        }
+        self
+    }
+}
+
+/// Returns `x` to the power of `p`, by repeated multiplication.
+fn pow<T: Float>(x: T, p: u32) -> T {
+    (0..p).fold(T::from_usize(1), |acc, _| acc * x)
+}
+
+/// Returns the `p`th root of `s`, which must be nonnegative, using
+/// Newton's method, as `core` has no `powf`. Starting at or above the
+/// root, each step goes down towards it, so we stop when one doesn't.
+fn root<T: Float>(s: T, p: u32) -> T {
+    let (one, n) = (T::from_usize(1), T::from_usize(p as usize));
+    // Zero is its own root, as is NaN, on which Newton's method stalls.
+    if s.partial_cmp(&T::default()) != Some(Ordering::Greater) {
+        return s;
+    }
+
+    let mut r = if s > one { s } else { one };
+    loop {
+        let next = ((n - one) * r + s / pow(r, p - 1)) / n;
+        match next.partial_cmp(&r) {
+            Some(Ordering::Less) => r = next,
+            _ => return r,
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! Quantisation, i.e. converting float tensors to int16 tensors and
+//! back, so that they can be multiplied with AMX's int16 ops, which
+//! are often faster than its float ones.
+
+use core::f32;
+
+use crate::space::Tensor;
+
+impl Tensor<f32> {
+    /// Quantise this tensor to int16, by multiplying each element by
+    /// `scale` and rounding to the nearest int, half away from zero.
+    /// Anything out of range saturates to [`i16::MIN`] or [`i16::MAX`],
+    /// rather than wrapping round to the other sign, and NaN becomes 0.
+    pub fn quantize_i16(&self, scale: f32) -> Tensor<i16> {
+        // `as` saturates, and maps NaN to 0, when casting floats to ints.
+        self.map(|&x| f32::math::round(x * scale) as i16)
+    }
+}
+
+impl Tensor<i16> {
+    /// Dequantise this tensor to float32, by dividing each element by
+    /// the `scale` it was quantised with. See [`Tensor::quantize_i16`].
+    pub fn dequantize_f32(&self, scale: f32) -> Tensor<f32> {
+        self.map(|&x| x as f32 / scale)
+    }
+
+    /// Dequantise this tensor to float16, by dividing each element by
+    /// the `scale` it was quantised with. This divides at 32 bits, and
+    /// only then narrows, so the result is as precise as it can be.
+    pub fn dequantize_f16(&self, scale: f32) -> Tensor<f16> {
+        self.map(|&x| (x as f32 / scale) as f16)
+    }
+}
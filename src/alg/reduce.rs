@@ -0,0 +1,153 @@
+//! Reductions, which collapse a tensor (or one of its dims) into the
+//! sum or mean of its elements, or whatever else a closure computes.
+
+use core::cmp::Ordering;
+use core::ops;
+
+use crate::invar::Float;
+use crate::space::{strides, Matrix, Tensor, UNUSED};
+
+impl<S> Tensor<S>
+where
+    S: ops::Add<Output = S> + Copy + Default,
+{
+    /// Returns the sum of every element of this tensor. A tensor with
+    /// no data sums to the default, i.e. zero for the number types.
+    pub fn sum(&self) -> S {
+        self.data()
+            .unwrap_or(&[])
+            .iter()
+            .fold(S::default(), |acc, &s| acc + s)
+    }
+
+    /// Returns the sums along dim `d`, i.e. a tensor of the same dims
+    /// as this one except that `d` is collapsed to length 1. So summing
+    /// a 3x4 matrix along dim 0 yields its 4 column sums, as a 1x4.
+    pub fn sum_axis(&self, d: usize) -> Tensor<S> {
+        self.reduce_axis(d, S::default(), |acc, &s| acc + s)
+    }
+}
+
+impl<T> Tensor<T> {
+    /// Folds every element of this tensor, in column-major order, into
+    /// an accumulator, starting from `init`. This is for reductions we
+    /// don't have a method for, e.g. the product, max, or argmax. With
+    /// no data, there's nothing to fold, so this just returns `init`.
+    pub fn fold<A, F: FnMut(A, &T) -> A>(&self, init: A, f: F) -> A {
+        self.data().unwrap_or(&[]).iter().fold(init, f)
+    }
+
+    /// Folds along dim `d`, i.e. returns a tensor of the same dims as
+    /// this one except that `d` is collapsed to length 1, where each
+    /// element is the fold, starting from `init`, of the elements along
+    /// `d` at that position, in order. So folding a 3x4 matrix with max
+    /// along dim 0 yields its 4 column maxes, as a 1x4.
+    pub fn reduce_axis<A: Clone, F: FnMut(A, &T) -> A>(&self, d: usize, init: A, mut f: F) -> Tensor<A> {
+        assert!(d < 8, "tensors have 8 dims, so dim {} is out of range", d);
+
+        let mut dims = self.dims();
+        dims[d] = UNUSED;
+
+        let data = self.data().map(|data| {
+            // In column-major order, the elements along `d` for a given
+            // output element are `stride` apart, in runs of `len`. Each
+            // run is preceded by `stride` elements of the dims before `d`.
+            let (stride, len) = (strides(&self.dims())[d], self.dims()[d].get() as usize);
+            (0..data.len() / len)
+                .map(|j| {
+                    let base = j % stride + j / stride * stride * len;
+                    (0..len).fold(init.clone(), |acc, k| f(acc, &data[base + k * stride]))
+                })
+                .collect()
+        });
+
+        Tensor { data, dims }
+    }
+}
+
+impl<T> Matrix<T>
+where
+    T: ops::Add<Output = T> + Copy,
+{
+    /// Returns the trace of this matrix, i.e. the sum of its diagonal,
+    /// or `None` if it isn't square, or has no data. In column-major
+    /// order, the diagonal of an NxN matrix is every (N+1)th element.
+    pub fn trace(&self) -> Option<T> {
+        let (n, data) = (self.0.vlen(), self.0.data()?);
+        if !self.is_square() {
+            return None;
+        }
+
+        data.iter()
+            .step_by(n + 1)
+            .copied()
+            .reduce(|acc, t| acc + t)
+    }
+}
+
+impl<T: PartialOrd + Copy> Tensor<T> {
+    /// Returns the largest element of this tensor, or `None` if it has
+    /// no data. NaNs are ignored, i.e. never the max, unless every one
+    /// of the elements is NaN, in which case this also returns `None`.
+    pub fn max(&self) -> Option<T> {
+        self.fold((0, None), extreme(Ordering::Greater))
+            .1
+            .map(|(_, x)| x)
+    }
+
+    /// Returns the smallest element of this tensor, or `None` if it has
+    /// no data, or only NaNs. As with [`Tensor::max`], NaNs are ignored.
+    pub fn min(&self) -> Option<T> {
+        self.fold((0, None), extreme(Ordering::Less))
+            .1
+            .map(|(_, x)| x)
+    }
+
+    /// Returns the (flat, column-major) index of the largest element of
+    /// this tensor, or `None` if it has no data, or only NaNs. If there
+    /// is a tie, this is the first. As with [`Tensor::max`], NaNs are
+    /// ignored.
+    pub fn argmax(&self) -> Option<usize> {
+        self.fold((0, None), extreme(Ordering::Greater))
+            .1
+            .map(|(i, _)| i)
+    }
+
+    /// Returns the indices along dim `d` of the largest elements, i.e.
+    /// a tensor of the same dims as this one except that `d` collapses
+    /// to length 1, as [`Tensor::reduce_axis`] does. So for a batch of
+    /// scores, one per column, this yields each column's top class. A
+    /// run along `d` of only NaNs yields 0, there being no other index.
+    pub fn argmax_axis(&self, d: usize) -> Tensor<usize> {
+        self.reduce_axis(d, (0, None), extreme(Ordering::Greater))
+            .map(|(_, best)| best.map_or(0, |(i, _)| i))
+    }
+}
+
+/// The state of a fold for [`extreme`]: the count of elements so far,
+/// and the index & value of the extreme one among them, if any.
+type Extreme<T> = (usize, Option<(usize, T)>);
+
+/// A fold step which keeps the index & value of the element which is
+/// `ord` to all the others, e.g. the max for [`Ordering::Greater`].
+/// NaNs, i.e. elements which aren't comparable even to themselves, are
+/// skipped, as are later ties.
+fn extreme<T: PartialOrd + Copy>(ord: Ordering) -> impl Fn(Extreme<T>, &T) -> Extreme<T> {
+    move |(i, best), &x| match best {
+        _ if x.partial_cmp(&x).is_none() => (i + 1, best),
+        Some((_, b)) if x.partial_cmp(&b) != Some(ord) => (i + 1, best),
+        _ => (i + 1, Some((i, x))),
+    }
+}
+
+impl<S> Tensor<S>
+where
+    S: Float,
+{
+    /// Returns the mean of every element of this tensor. This is only
+    /// defined for floats, so that no one is surprised by truncation.
+    /// A tensor with no data has a mean of NaN, i.e. zero over zero.
+    pub fn mean(&self) -> S {
+        self.sum() / S::from_usize(self.data().map_or(0, <[S]>::len))
+    }
+}
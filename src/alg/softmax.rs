@@ -0,0 +1,81 @@
+//! The softmax function, which turns a tensor of scores, e.g. logits,
+//! into probabilities along one of its dims, for inference on models.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::f64;
+use core::f64::consts::LN_2;
+
+use crate::invar::Float;
+use crate::space::{strides, Tensor};
+
+impl<T: Float> Tensor<T> {
+    /// Returns the softmax of this tensor along dim `d`, i.e. each
+    /// element `x` becomes `exp(x)` over the sum of `exp` of every
+    /// element along `d` at its position, so that each of those sums
+    /// to 1. The max along `d` is subtracted before `exp`, so that no
+    /// large input overflows to infinity, and `-inf` becomes 0, as long
+    /// as there's something larger: if not, i.e. it's all `-inf`, or
+    /// if there's a NaN or `+inf`, then the result is NaN along `d`. A
+    /// tensor with no data yields the same.
+    pub fn softmax(&self, d: usize) -> Tensor<T> {
+        assert!(d < 8, "tensors have 8 dims, so dim {} is out of range", d);
+
+        // In column-major order, the elements along `d` for a given
+        // position are `stride` apart, in runs of `len`, as in
+        // `reduce_axis`. So element `i` is at position `at(i)`.
+        let (stride, len) = (strides(&self.dims)[d], self.dims[d].get() as usize);
+        let at = |i: usize| i % stride + i / (stride * len) * stride;
+
+        let data = self.data().map(|data| {
+            let mut max = vec![T::from_f64(f64::NEG_INFINITY); data.len() / len];
+            data.iter().enumerate().for_each(|(i, &x)| {
+                if x > max[at(i)] {
+                    max[at(i)] = x;
+                }
+            });
+
+            let exps: Vec<T> = data
+                .iter()
+                .enumerate()
+                .map(|(i, &x)| T::from_f64(exp((x - max[at(i)]).to_f64())))
+                .collect();
+            let mut sums = vec![T::default(); data.len() / len];
+            exps.iter()
+                .enumerate()
+                .for_each(|(i, &e)| sums[at(i)] = sums[at(i)] + e);
+
+            exps.iter()
+                .enumerate()
+                .map(|(i, &e)| e / sums[at(i)])
+                .collect()
+        });
+
+        Tensor { data, dims: self.dims }
+    }
+}
+
+/// `e` to the power of `x`, since `core` has no `exp`. Splitting `x`
+/// into `k ln 2 + r`, where `|r| <= ln 2 / 2`, `exp x = 2^k exp r`, and
+/// the series for `exp r` converges quickly, as `r` is so small.
+fn exp(x: f64) -> f64 {
+    if x.is_nan() {
+        return x;
+    } else if x > 709.8 {
+        return f64::INFINITY;
+    } else if x < -745.2 {
+        return 0.;
+    }
+
+    let k = f64::math::floor(x / LN_2 + 0.5) as i32;
+    let r = x - k as f64 * LN_2;
+    let (sum, _) = (1..20).fold((1., 1.), |(sum, term), n| {
+        let term = term * r / n as f64;
+        (sum + term, term)
+    });
+
+    // Scale by 2^k in two halves, since either end of the range of `k`
+    // is past the exponent of any normal `f64`, though the result isn't.
+    let pow2 = |k: i32| f64::from_bits(((1023 + k) as u64) << 52);
+    sum * pow2(k / 2) * pow2(k - k / 2)
+}
@@ -2,20 +2,21 @@
 //! used for enqueueing instructions onto the address bus, to execute
 //! on the AMX coprocessor.
 
-use core::{arch::asm, mem::MaybeUninit};
+#[cfg(target_arch = "aarch64")] use core::arch::asm;
 
 use super::regs::RegSet;
 
 /// Write 64 bytes to a vector register in set x/y (0-7) or z (0-63).
 pub(super) fn set_vector(set: RegSet, reg: u64, ptr: *const [u8]) {
-    let op = match set {
-        RegSet::X => 0,
-        RegSet::Y => 1,
-        RegSet::Z => 4,
-    };
     let operand = fmt_offset_ptr::<64>(reg, (ptr.cast::<u64>()) as u64);
 
-    unsafe { emit_op(op, operand) };
+    unsafe {
+        match set {
+            RegSet::X => emit_op::<0>(operand),
+            RegSet::Y => emit_op::<1>(operand),
+            RegSet::Z => emit_op::<4>(operand),
+        }
+    };
 }
 
 /// Write 512 bytes to regset X/Y, or 4096 to Z. This is *not* atomic,
@@ -31,23 +32,23 @@ pub fn set_matrix(set: RegSet, data: &[u8]) {
             0..64
         }
     }
-    .for_each(|i| set_vector(set, i, &data[(i * 8) as usize..((i + 1) * 8) as usize] as *const [u8]))
+    // Each register is 64 bytes, so register `i` is the `i`th 64 bytes.
+    .for_each(|i| set_vector(set, i, &data[(i * 64) as usize..((i + 1) * 64) as usize] as *const [u8]))
 }
 
 /// Read 64 bytes from a vector register in set x/y (0-7) or z (0-63).
 pub fn get_vector(set: RegSet, reg: u64) -> [u8; 64] {
-    let mut buf: [u8; 64] = unsafe { MaybeUninit::uninit().assume_init() };
+    let mut buf = [0u8; 64];
     let ptr: *mut [u8; 64] = &mut buf;
 
     let operand = fmt_offset_ptr::<64>(reg, ptr as u64);
-    let op = match set {
-        RegSet::X => 2,
-        RegSet::Y => 3,
-        RegSet::Z => 5,
-    };
 
     unsafe {
-        emit_op(op, operand);
+        match set {
+            RegSet::X => emit_op::<2>(operand),
+            RegSet::Y => emit_op::<3>(operand),
+            RegSet::Z => emit_op::<5>(operand),
+        }
     }
 
     buf
@@ -56,25 +57,23 @@ pub fn get_vector(set: RegSet, reg: u64) -> [u8; 64] {
 /// Read 512 bytes to regset X/Y, or 4096 from Z. This is *not* atomic,
 /// but iterates over all the vector registers: 8 for X/Y & 64 for Z.
 pub fn get_matrix_512(set: RegSet) -> [u8; 512] {
-    let mut buf: [u8; 512] = unsafe { MaybeUninit::uninit().assume_init() };
+    let mut buf = [0u8; 512];
     let ptr: *mut [u8; 512] = &mut buf;
 
-    let op = match set {
-        RegSet::X => 2,
-        RegSet::Y => 3,
-        RegSet::Z => {
-            #[cfg(feature = "debug")]
-            panic!("passed invalid regset `z` to `get_matrix_512`");
-            return [0; 512];
-        }
-    };
+    if set == RegSet::Z {
+        #[cfg(feature = "debug")]
+        panic!("passed invalid regset `z` to `get_matrix_512`");
+        #[cfg(not(feature = "debug"))]
+        return [0; 512];
+    }
 
     (0..8).for_each(|reg| unsafe {
-        emit_op(
-            op,
-            // Safe: Bump `ptr` by 64 each time. 512 (`ptr` alloc size) / 8 (iters) = 64.
-            fmt_offset_ptr::<64>(reg, ptr.offset((reg * 64) as isize) as u64),
-        );
+        // Safe: Bump `ptr` by 64 each time. 512 (`ptr` alloc size) / 8 (iters) = 64.
+        let operand = fmt_offset_ptr::<64>(reg, ptr.offset((reg * 64) as isize) as u64);
+        match set {
+            RegSet::Y => emit_op::<3>(operand),
+            _ => emit_op::<2>(operand),
+        }
     });
 
     buf
@@ -82,98 +81,181 @@ pub fn get_matrix_512(set: RegSet) -> [u8; 512] {
 
 /// Read a 4096-byte 64x64 matrix from regset Z, the largest of the 3.
 pub fn get_matrix_4096() -> [u8; 4096] {
-    let mut buf: [u8; 4096] = unsafe { MaybeUninit::uninit().assume_init() };
+    let mut buf = [0u8; 4096];
     let ptr: *mut [u8; 4096] = &mut buf;
 
     (0..64).for_each(|reg| unsafe {
-        emit_op(
-            5,
+        emit_op::<5>(
             // Safe: Bump `ptr` by 64 each time. 4096 (`ptr` alloc size) / 64 (iters) = 64.
-            fmt_offset_ptr::<64>(reg, unsafe { ptr.offset((reg * 64) as isize) } as u64),
+            fmt_offset_ptr::<64>(reg, ptr.offset((reg * 64) as isize) as u64),
         )
     });
 
     buf
 }
 
-/// ## Mathematical ops
-/// These ops take one/more register as input and one/more as output.
+/// Write 64 bytes to a vector register in set z (0-63), interleaved,
+/// i.e. split across the pair of rows `reg & !1` and `reg | 1`.
+pub fn set_vector_interleaved(reg: u64, ptr: *const [u8]) {
+    debug_assert!(reg < 64, "reg must be in 0..64 but was {}", reg);
+
+    unsafe { emit_op::<6>(fmt_offset_ptr::<64>(reg, ptr.cast::<u64>() as u64)) };
+}
+
+/// Read 64 bytes from a vector register in set z (0-63), interleaved,
+/// i.e. exactly those written by [`set_vector_interleaved`].
+pub fn get_vector_interleaved(reg: u64) -> [u8; 64] {
+    debug_assert!(reg < 64, "reg must be in 0..64 but was {}", reg);
+
+    let mut buf = [0u8; 64];
+    let ptr: *mut [u8; 64] = &mut buf;
+
+    unsafe { emit_op::<7>(fmt_offset_ptr::<64>(reg, ptr as u64)) };
+
+    buf
+}
+
+// ## Mathematical ops
+// These ops take one/more register as input and one/more as output.
+// Each multiply reads one register of X and one of Y, `x` and `y`,
+// and writes or adds their outer product to `z`.
 
-/// Matrix multiplies X and Y as float16, writing the product to `z`.
-pub fn matrix_mul_f16() {
+/// Multiplies register `x` of X by register `y` of Y as float16,
+/// writing their 32x32 outer product over `z`, whatever was in it.
+pub fn matrix_mul_f16(x: u64, y: u64) {
     // Safe: This operation will simply result in an empty matrix if
-    // the input matrices are empty. No possible input is invalid.
-    unsafe { emit_op(21, 0) }
+    // the input matrices are empty. No possible input is invalid. Bit
+    // 27 skips reading `z`, so the product overwrites it.
+    unsafe { emit_op::<15>((1 << 27) | fmt_xy(x, y)) }
 }
 
-/// Matrix multiplies X and Y as int16, writing the product to `z`.
-pub fn matrix_mul_i16() {
+/// Multiplies register `x` of X by register `y` of Y as int16,
+/// writing their 32x32 outer product over `z`, as int16, so it wraps
+/// on overflow. See [`matrix_mul_add_i16`].
+pub fn matrix_mul_i16(x: u64, y: u64) {
+    // Safe: This operation will simply result in an empty matrix if
+    // the input matrices are empty. No possible input is invalid. Bit
+    // 27 skips reading `z`, so the product overwrites it.
+    unsafe { emit_op::<14>((1 << 27) | fmt_xy(x, y)) }
+}
+
+/// Multiplies register `x` of X by register `y` of Y as float16,
+/// adding their 32x32 outer product to `z`.
+pub fn matrix_mul_add_f16(x: u64, y: u64) {
     // Safe: This operation will simply result in an empty matrix if
     // the input matrices are empty. No possible input is invalid.
-    unsafe { emit_op(20, 0) }
+    unsafe { emit_op::<15>(fmt_xy(x, y)) }
 }
 
-/// Matrix multiplies X and Y as float16, adding the product to `z`.
-pub fn matrix_mul_add_f16() {
+/// Multiplies register `x` of X by register `y` of Y as int16,
+/// adding their 32x32 outer product to `z`.
+/// Beware: `z` is int16 too, so each sum wraps on overflow, which a
+/// deep reduction of int16s soon does. (The op can accumulate into
+/// int32 instead, by setting bit 62, but `z` is then laid out
+/// differently, so that isn't exposed yet.)
+pub fn matrix_mul_add_i16(x: u64, y: u64) {
     // Safe: This operation will simply result in an empty matrix if
     // the input matrices are empty. No possible input is invalid.
-    unsafe { emit_op(15, 0) }
+    unsafe { emit_op::<14>(fmt_xy(x, y)) }
+}
+
+/// Multiplies register `x` of X by register `y` of Y as float32,
+/// writing their outer product over `z`. Only 16 floats fit in each
+/// register in 32-bit mode, so this is a 16x16 product, which is
+/// written to every 4th row of `z`.
+pub fn matrix_mul_f32(x: u64, y: u64) {
+    // Safe: This operation will simply result in an empty matrix if
+    // the input matrices are empty. No possible input is invalid. Bit
+    // 27 skips reading `z`, so the product overwrites it.
+    unsafe { emit_op::<12>((1 << 27) | fmt_xy(x, y)) }
 }
 
-/// Matrix multiplies X and Y as int16, adding the product to `z`.
-pub fn matrix_mul_add_i16() {
+/// Multiplies register `x` of X by register `y` of Y as float32,
+/// adding their outer product to `z`. See [`matrix_mul_f32`] for the
+/// reduced dimensions in 32-bit mode.
+pub fn matrix_mul_add_f32(x: u64, y: u64) {
     // Safe: This operation will simply result in an empty matrix if
     // the input matrices are empty. No possible input is invalid.
-    unsafe { emit_op(14, 0) }
+    unsafe { emit_op::<12>(fmt_xy(x, y)) }
 }
 
-/// # Configuration ops
+// ## Configuration ops
 
 /// Enables the AMX coprocessor. Unsafe: Caller must manage state.
+/// This is only called where `AmxCtx::get` can succeed.
+#[cfg(all(target_arch = "aarch64", target_os = "macos", target_pointer_width = "64"))]
 pub(super) unsafe fn set() {
-    emit_op(17, 0)
+    op_imm::<17, 0>()
 }
 
 /// Disables the AMX coprocessor. Unsafe: Caller must manage state.
 pub unsafe fn clr() {
-    emit_op(17, 1)
+    op_imm::<17, 1>()
 }
 
-/// Enqueue an AMX instruction, passing `op` and `operand` via regs.
-unsafe fn emit_op(op: u8, operand: u64) {
+/// Enqueue an AMX instruction, passing `operand` via a register. The
+/// `OP` is part of the instruction word, so it must be a constant.
+unsafe fn emit_op<const OP: u8>(operand: u64) {
+    // AMX only exists on aarch64, so there's nothing to emit elsewhere.
+    // (Nor can this be reached: see `AmxCtx::get`.) We pin the operand
+    // to `x0`, so that the word can be encoded as a constant up front.
+    #[cfg(target_arch = "aarch64")]
     asm!(
-        // The convention is: `0x00201000 | ((op & 0x1F) << 5) | (operand & 0x1F)`.
-        // Note: Formatting is strange, but means params parse correctly as numbers.
-        // https://gist.github.com/dougallj/7a75a3be1ec69ca550e7c36dc75e0d6f#file-aarch64_amx-py-L53.
-        ".word 0x00201000 + ({op} << 5) + (0{operand} & 0xf) + (0{operand} >> 4) * 10",
-        op = in(reg) op,
-        operand = in(reg) operand,
+        ".word {word}",
+        word = const encode_word(OP, 0),
+        in("x0") operand,
         options(nostack, preserves_flags),
     );
+    #[cfg(not(target_arch = "aarch64"))]
+    unreachable!(
+        "cannot emit AMX word {:#010x} (x0 = {:#x}) off aarch64",
+        encode_word(OP, 0),
+        operand
+    );
 }
 
 /// Enqueue an AMX instruction with immediate (constant) parameters.
+/// This saves moving the operand into a register, but only op 17,
+/// i.e. `set` & `clr`, reads its operand field as an immediate: every
+/// other op reads it as a register number, so must use [`emit_op`].
 unsafe fn op_imm<const OP: u8, const OPERAND: u8>() {
+    // See `emit_op`: there is nothing to emit off aarch64.
+    #[cfg(target_arch = "aarch64")]
     asm!(
-        ".word 0x00201000 + ({op} << 5) + {operand}",
-        op = const OP,
-        operand = const OPERAND,
+        ".word {word}",
+        word = const encode_word(OP, OPERAND),
         options(nostack, preserves_flags),
     );
+    #[cfg(not(target_arch = "aarch64"))]
+    unreachable!("cannot emit AMX word {:#010x} off aarch64", encode_word(OP, OPERAND));
 }
 
-/// Encode the offset and size into one 64bit int, as is required by
-/// the undocumented AMX API:
-fn fmt_offset<const SIZE: u64>(offset: u64) -> u64 {
-    debug_assert!(offset < 64);
+/// Encode an AMX instruction as the 32-bit word the CPU decodes. AMX
+/// ops live in an unused corner of the aarch64 encoding space, so the
+/// word is `0x00201000`, then the op in bits 5-9, then the operand in
+/// bits 0-4. The operand is either the number of the general-purpose
+/// register which holds the real operand, or, for some ops, a 5-bit
+/// immediate. This is pure, so the encodings can be checked anywhere.
+/// See: https://gist.github.com/dougallj/7a75a3be1ec69ca550e7c36dc75e0d6f.
+pub(crate) const fn encode_word(op: u8, operand: u8) -> u32 {
+    0x0020_1000 | ((op as u32 & 0x1F) << 5) | (operand as u32 & 0x1F)
+}
+
+/// Encode the registers of X and Y which a multiply reads as their
+/// byte offsets into each set: X's in bits 10-18 and Y's in 0-8.
+fn fmt_xy(x: u64, y: u64) -> u64 {
+    debug_assert!(x < 8 && y < 8);
 
-    (offset << 56) | (SIZE << 62)
+    ((x * 64) << 10) | (y * 64)
 }
 
 /// Encode the offset and size AND pointer into one 64bit int, as is
 /// required by the undocumented AMX API:
 fn fmt_offset_ptr<const SIZE: u64>(offset: u64, ptr: u64) -> u64 {
     debug_assert!(offset < 64);
+    // The pointer only gets the low 56 bits, so any set above that would
+    // be dropped by the mask below, and AMX would use the wrong address.
+    debug_assert!(ptr >> 56 == 0, "pointer {:#x} does not fit in 56 bits", ptr);
 
-    (offset << 56) | (SIZE << 62) | (ptr as u64 & 0x00FF_FFFF_FFFF_FFFF)
+    (offset << 56) | (SIZE << 62) | (ptr & 0x00FF_FFFF_FFFF_FFFF)
 }
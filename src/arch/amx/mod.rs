@@ -1,21 +1,55 @@
+//! This module is a low-level wrapper over the M1's AMX coprocessor,
+//! for fast large linear algebra over vectors and matrices. Its use
+//! is simple: obtain an [`AmxCtx`] by calling `get()`, and drop it
+//! once you're done, which disables AMX again. Or, to keep AMX enabled
+//! for the life of the thread, e.g. in each worker of a thread pool,
+//! run each piece of work in [`with_amx`], which enables it only once.
+
 mod bus;
+mod ops;
 mod regs;
+mod scalar;
 
 use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
 
-pub use regs::RegSet;
-
-/// This module is a low-level wrapper over the M1's AMX coprocessor,
-/// for fast large linear algebra over vectors and matrices. Its use
-/// is simple: obtain an [`AmxHandle`] by calling `acquire()`.
+pub use ops::{AmxOps, MulType, RegErr};
+pub use regs::{RegSet, XVec, YVec, ZVec};
+pub use scalar::{AmxScalar, DEPTH};
 
 /// AMX must be enabled before use, but should only be enabled one
 /// time per thread. We check this before initialising an instance
-/// of [`AmxHandle`], to enforce this invariant.
+/// of [`AmxCtx`], to enforce this invariant.
 #[thread_local]
-static HANDLE: Cell<Option<AmxHandle>> = Cell::new(None);
+static OWNER: Cell<Owner> = Cell::new(Owner::Nobody);
+
+/// What enabled AMX in this thread, if anything, and so what may
+/// disable it again, and whether [`AmxCtx::get`] may take it over.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Owner {
+    /// AMX is disabled.
+    Nobody,
+    /// A live [`AmxCtx`] from [`AmxCtx::get`], which disables AMX when
+    /// it's dropped. No other context may be handed out meanwhile.
+    Ctx,
+    /// [`with_amx`], which leaves AMX enabled for the next call to
+    /// reuse. `get` may take this over, since nothing is using it.
+    Thread,
+    /// As `Thread`, but a call to `with_amx` is running, so `get` may
+    /// not take it over, as dropping that context would disable AMX
+    /// under the call.
+    Busy,
+}
 
-/// An error returned by [`AmxHandle::get`], representing failure
+/// Which registers of each set, X, Y and Z in turn, have been loaded
+/// since AMX was enabled in this thread, one bit per register. This
+/// is only for [`AmxCtx::loaded_rows`], to help debug missing loads.
+#[thread_local]
+static LOADED: Cell<[u64; 3]> = Cell::new([0; 3]);
+
+/// An error returned by [`AmxCtx::get`], representing failure
 /// modes which prevent us from initialising AMX.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 #[non_exhaustive]
@@ -23,55 +57,200 @@ pub enum AmxErr {
     /// The target triple does not support AMX. Unless otherwise
     /// specified, this is the machine compiling the code.
     Incompatible,
+    /// AMX is already in use in this thread, i.e. another context from
+    /// [`AmxCtx::get`] is still alive, or this was called from inside
+    /// [`with_amx`]. It must be dropped, or return, before we can get a
+    /// new one. `with_amx` itself never returns this.
+    Exists,
+    /// The target supports AMX, but the CPU we're running on does not,
+    /// e.g. it's a newer or older chip than the target anticipated.
+    Unsupported,
 }
 
-/// A handle represents an initialised AMX instance in this thread.
+impl fmt::Display for AmxErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AmxErr::Incompatible => "AMX is not available on this target",
+            AmxErr::Exists => "AMX is already enabled in this thread",
+            AmxErr::Unsupported => "AMX is not supported by this CPU",
+        })
+    }
+}
+
+/// This is the same trait as `std::error::Error`, so `AmxErr` works
+/// with `?` and error chains in `std` code without needing `std` here.
+impl core::error::Error for AmxErr {}
+
+/// A context represents an initialised AMX instance in this thread.
 /// It is scoped to a particular thread and thus specifically does
 /// not implement [`Send`] or [`Sync`]. This is a zero-sized type,
 /// and therefore does not consume any memory besides a flicked bit
 /// within the [`Option<T>`] that inevitably contains it.
 ///
-/// - [`self::ops`] implements the instructions.
-pub struct AmxHandle;
+/// - [`AmxOps`] implements the instructions.
+/// - [`Drop`] disables AMX, so it can't be left enabled by mistake.
+pub struct AmxCtx {
+    // Private, so that a context can only be made by `get` (or in this
+    // module), and a raw pointer, so that it's neither `Send` nor `Sync`.
+    _thread: PhantomData<*const ()>,
+}
 
-impl AmxHandle {
-    /// Obtain an [`AmxHandle`] by enabling AMX for this thread. This
-    /// [`AmxHandle`] can then be used to run AMX instructions. This
+impl AmxCtx {
+    /// Obtain an [`AmxCtx`] by enabling AMX for this thread. This
+    /// [`AmxCtx`] can then be used to run AMX instructions. This
     /// ensures that the only way to use the AMX processor is via the
     /// path that enables it - and checks it wasn't already enabled.
+    /// If [`with_amx`] left AMX enabled, and isn't running, this takes
+    /// that over, rather than enabling it again, so dropping the context
+    /// disables it, as for any other.
     pub fn get() -> Result<Self, AmxErr> {
-        #[cfg(not(all(target_arch = "aarch64", target_os = "macos", target_pointer_width = "64")))]
-        {
-            // Target is not compatible. Return an Err().
-            Err(AmxErr::Incompatible)
+        match OWNER.get() {
+            // There is already a context in this thread. Handing
+            // out another would break the invariant (see above).
+            Owner::Ctx | Owner::Busy => return Err(AmxErr::Exists),
+            Owner::Thread => OWNER.set(Owner::Ctx),
+            Owner::Nobody => enable(Owner::Ctx)?,
         }
 
-        #[cfg(all(target_arch = "aarch64", target_os = "macos", target_pointer_width = "64"))]
-        {
-            if let Some(handle) = HANDLE.take() {
-                // Return an AmxHandle to prove the above block was
-                // executed exactly once (see doc comment).
-                Ok(Self)
-            } else {
-                // Safe: We finally know that AMX is supported, and
-                // not already enabled ITT, so enable it.
-                unsafe { Self::set() };
-                HANDLE.set(Some(Self));
-
-                Ok(Self)
-            }
+        Ok(AmxCtx { _thread: PhantomData })
+    }
+}
+
+/// Enable AMX in this thread, on behalf of `owner`, if the target has
+/// it, resetting [`LOADED`], since the registers start afresh.
+fn enable(owner: Owner) -> Result<(), AmxErr> {
+    #[cfg(not(all(target_arch = "aarch64", target_os = "macos", target_pointer_width = "64")))]
+    {
+        // Target is not compatible. Return an Err().
+        let _ = owner;
+        Err(AmxErr::Incompatible)
+    }
+
+    #[cfg(all(target_arch = "aarch64", target_os = "macos", target_pointer_width = "64"))]
+    {
+        // Safe: We finally know that AMX is supported, and the
+        // caller checked it isn't already enabled ITT, so enable it.
+        unsafe { bus::set() };
+        OWNER.set(owner);
+        LOADED.set([0; 3]);
+
+        Ok(())
+    }
+}
+
+impl AmxCtx {
+    /// Whether AMX is enabled in this thread, i.e. whether there's a
+    /// live context, or [`with_amx`] has enabled it. Note that, unless
+    /// it's the former, or inside a call to `with_amx`, [`AmxCtx::get`]
+    /// succeeds anyway, by taking over what `with_amx` left. This takes
+    /// no context, since with one in hand, the answer is always yes.
+    pub fn is_active() -> bool {
+        OWNER.get() != Owner::Nobody
+    }
+
+    /// Which registers of `set` have been loaded since AMX was enabled
+    /// in this thread, as a bitmask, i.e. bit `i` is set if register
+    /// `i` has been, e.g. by [`AmxOps::ldx`], or all of them by
+    /// [`AmxOps::set_matrix`]. This is for debugging, e.g. to catch a
+    /// multiply that reads a row which was never loaded. Only loads
+    /// count, not the ops which write their results to Z.
+    pub fn loaded_rows(&self, set: RegSet) -> u64 {
+        LOADED.get()[set as usize]
+    }
+
+    /// Mark the registers of `set` in the bitmask `rows` as loaded.
+    fn mark_loaded(&self, set: RegSet, rows: u64) {
+        let mut loaded = LOADED.get();
+        loaded[set as usize] |= rows;
+        LOADED.set(loaded);
+    }
+}
+
+impl Drop for AmxCtx {
+    /// Disable AMX for the current thread. This is done on drop, so
+    /// we can count on the invariant that it cannot happen without
+    /// AMX having been initialised, nor be forgotten once it has.
+    fn drop(&mut self) {
+        // Unset `OWNER`, so a new context may be created. (This
+        // one cannot now be used, as it is being dropped.)
+        OWNER.set(Owner::Nobody);
+
+        // Safe: AMX is supported and context initialised: see above.
+        unsafe { bus::clr() };
+    }
+}
+
+/// Run `f` with this thread's [`AmxCtx`], enabling AMX first only if
+/// it isn't already, and leaving it enabled afterwards, for the next
+/// call to reuse. So this may be called over and over, or nested, in
+/// one thread, without ever returning [`AmxErr::Exists`], and without
+/// the cost of enabling AMX each time. A context from [`AmxCtx::get`]
+/// is reused too, if there is one, and `get` can take over what this
+/// left enabled, as long as it isn't called from inside `f`. It's only
+/// an error if AMX can't be enabled at all, i.e. [`AmxErr::Incompatible`]
+/// or `Unsupported`.
+pub fn with_amx<R>(f: impl FnOnce(&mut AmxCtx) -> R) -> Result<R, AmxErr> {
+    let owner = match OWNER.get() {
+        Owner::Nobody => {
+            enable(Owner::Thread)?;
+            Owner::Thread
         }
+        owner => owner,
+    };
+
+    // Mark AMX as in use while `f` runs, so that `get` can't take it
+    // over meanwhile, and put the owner back after, even on unwinding.
+    let _restore = Restore(owner);
+    if owner == Owner::Thread {
+        OWNER.set(Owner::Busy);
     }
 
-    /// Disable AMX for the current thread. This must be private, and
-    /// must be an instance method, so we can count on the invariant
-    /// that it cannot be called without AMX having been initialised.
-    fn disable(self) {
-        // Unset `AMX_ENABLED`, so a new handle may be created. (This
-        // one cannot now be used, as `self` is consumed by this fn.)
-        HANDLE.set(false);
+    // The context is never dropped, as that would disable AMX, whether
+    // this thread owns it, or another context does. Since it's zero-
+    // sized, neither is there anything to leak: the owner is the flag.
+    Ok(f(&mut ManuallyDrop::new(AmxCtx { _thread: PhantomData })))
+}
+
+/// Puts the owner of AMX back as it was when [`with_amx`] was called,
+/// on drop, i.e. when it returns, or `f` unwinds.
+struct Restore(Owner);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        OWNER.set(self.0);
+    }
+}
 
-        // Safe: AMX is supported and handle initialised: see above.
-        unsafe { self.clr() };
+/// Run one throwaway multiply on AMX, in [`with_amx`], so that the
+/// first real op doesn't pay for it, e.g. before timing a benchmark.
+/// AMX is left enabled afterwards, as `with_amx` always leaves it, so
+/// it stays warm for later calls on this thread. It's idempotent per
+/// thread: calling it again only reruns the multiply. All of X, Y and
+/// Z are zeroed before the multiply, and Z again after it, so nothing
+/// is left in them. It's an error in the same cases as `with_amx`.
+pub fn warmup() -> Result<(), AmxErr> {
+    with_amx(|amx| {
+        amx.clear(RegSet::X);
+        amx.clear(RegSet::Y);
+        amx.clear_z();
+        amx.matrix_mul_f32();
+        amx.clear_z();
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_amx_nests() {
+        // Whether or not AMX is available here, nesting must reuse the
+        // outer context, rather than fail to make a second one.
+        for _ in 0..2 {
+            match with_amx(|_| with_amx(|_| AmxCtx::is_active())) {
+                Ok(inner) => assert_eq!(inner, Ok(true)),
+                Err(err) => assert_ne!(err, AmxErr::Exists),
+            }
+        }
     }
 }
@@ -0,0 +1,374 @@
+//! This module defines the AMX instructions as the trait [`AmxOps`],
+//! implemented by an [`AmxCtx`], so that they can only be issued
+//! once AMX has been enabled for this thread. Every op is emitted by
+//! [`super::bus`], which is the one place the encodings live.
+
+use alloc::vec::Vec;
+use core::{fmt, mem};
+
+use super::regs::RegSet;
+use super::scalar::AmxScalar;
+use super::{bus, AmxCtx};
+
+/// The type of a matrix multiply, i.e. which scalar type X and Y are
+/// read as, and whether the product overwrites `z` or is added to it.
+/// This lets the op be chosen at runtime, e.g. from a type tag.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MulType {
+    /// Multiply as float16, writing the product to `z`.
+    F16,
+    /// Multiply as int16, writing the product to `z`, as int16.
+    I16,
+    /// Multiply as float32, writing the product to `z`.
+    F32,
+    /// Multiply as float16, adding the product to `z`.
+    F16Add,
+    /// Multiply as int16, adding the product to `z`, as int16, so it
+    /// wraps on overflow.
+    I16Add,
+    /// Multiply as float32, adding the product to `z`.
+    F32Add,
+}
+
+/// An error returned by [`AmxOps::set_matrix_typed`] and
+/// [`AmxOps::set_vector_long`], when the data is the wrong size for the
+/// register set it's meant to be written to.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum RegErr {
+    /// The data isn't exactly as many bytes as the set holds, i.e. 512
+    /// for X/Y, or 4096 for Z. Both sizes are given in bytes.
+    SizeMismatch { expected: usize, got: usize },
+    /// The data is more bytes than the set holds, i.e. `capacity`, so
+    /// it can't be spread over its registers. Both are given in bytes.
+    TooLong { capacity: usize, got: usize },
+}
+
+impl fmt::Display for RegErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegErr::SizeMismatch { expected, got } => {
+                write!(f, "register set holds {} bytes, but was given {}", expected, got)
+            }
+            RegErr::TooLong { capacity, got } => {
+                write!(f, "register set holds at most {} bytes, but was given {}", capacity, got)
+            }
+        }
+    }
+}
+
+impl core::error::Error for RegErr {}
+
+/// The instructions exposed by the AMX coprocessor. Taking `&self`
+/// means that nothing can be issued without a live implementor, i.e.
+/// a proof that AMX has been enabled. The ops generic over a scalar
+/// type are only for sized implementors, so that `&dyn AmxOps` works,
+/// e.g. to swap in a mock of the coprocessor.
+pub trait AmxOps {
+    /// Write 64 bytes to register `reg` of X, which has 8 of them.
+    fn ldx(&self, reg: u8, data: &[u8; 64]);
+
+    /// Write 64 bytes to register `reg` of Y, which has 8 of them.
+    fn ldy(&self, reg: u8, data: &[u8; 64]);
+
+    /// Write 64 bytes to register `reg` of Z, which has 64 of them.
+    fn ldz(&self, reg: u8, data: &[u8; 64]);
+
+    /// Write 64 bytes to register `reg` of Z, interleaved with its
+    /// neighbour. See [`bus::set_vector_interleaved`].
+    fn ldzi(&self, reg: u8, data: &[u8; 64]);
+
+    /// Read 64 bytes from register `reg` of X, which has 8 of them.
+    fn stx(&self, reg: u8) -> [u8; 64];
+
+    /// Read 64 bytes from register `reg` of Y, which has 8 of them.
+    fn sty(&self, reg: u8) -> [u8; 64];
+
+    /// Read 64 bytes from register `reg` of Z, which has 64 of them.
+    fn stz(&self, reg: u8) -> [u8; 64];
+
+    /// Read 64 bytes from register `reg` of Z, as they were written by
+    /// [`AmxOps::ldzi`]. See [`bus::get_vector_interleaved`].
+    fn stzi(&self, reg: u8) -> [u8; 64];
+
+    /// Write 512 bytes to regset X/Y, or 4096 to Z. See [`bus::set_matrix`].
+    fn set_matrix(&self, set: RegSet, data: &[u8]);
+
+    /// Write scalars of type `T` to regset X/Y, or Z, in register order,
+    /// i.e. as [`AmxOps::get_matrix_512_as`] reads them back. Unlike the
+    /// raw [`AmxOps::set_matrix`], the size is checked in every build,
+    /// so e.g. 255 halfs for X is an error, rather than an overread.
+    fn set_matrix_typed<T: AmxScalar>(&self, set: RegSet, data: &[T]) -> Result<(), RegErr>
+    where
+        Self: Sized,
+    {
+        let expected = if set == RegSet::Z { 4096 } else { 512 };
+        let (size, got) = (mem::size_of::<T>(), mem::size_of_val(data));
+        if got != expected {
+            return Err(RegErr::SizeMismatch { expected, got });
+        }
+
+        let mut buf = [0u8; 4096];
+        data.iter()
+            .enumerate()
+            .for_each(|(i, &v)| T::put(&mut buf, i * size, v));
+        self.set_matrix(set, &buf[..expected]);
+
+        Ok(())
+    }
+
+    /// Write scalars of type `T` to consecutive registers of regset X/Y,
+    /// or Z, from the first, i.e. one register's worth to each in turn,
+    /// with the last zero-padded, e.g. 128 halfs fill four rows of X.
+    /// Unlike [`AmxOps::set_matrix_typed`], the data may be shorter than
+    /// the set, but it's an error if it's longer, i.e. than 8 registers
+    /// of X/Y, or 64 of Z. The registers after the data are left as is.
+    fn set_vector_long<T: AmxScalar>(&self, set: RegSet, data: &[T]) -> Result<(), RegErr>
+    where
+        Self: Sized,
+    {
+        let capacity = set.regs() as usize * 64;
+        let got = mem::size_of_val(data);
+        if got > capacity {
+            return Err(RegErr::TooLong { capacity, got });
+        }
+
+        let size = mem::size_of::<T>();
+        for (reg, chunk) in data.chunks(T::TILE).enumerate() {
+            let mut buf = [0u8; 64];
+            chunk
+                .iter()
+                .enumerate()
+                .for_each(|(i, &v)| T::put(&mut buf, i * size, v));
+            match set {
+                RegSet::X => self.ldx(reg as u8, &buf),
+                RegSet::Y => self.ldy(reg as u8, &buf),
+                RegSet::Z => self.ldz(reg as u8, &buf),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Zero every register of regset X/Y, or Z, by writing zeros to it
+    /// with [`AmxOps::set_matrix`]. Registers keep their contents from
+    /// one op to the next, so this is how to start afresh.
+    fn clear(&self, set: RegSet) {
+        let size = if set == RegSet::Z { 4096 } else { 512 };
+        self.set_matrix(set, &[0u8; 4096][..size]);
+    }
+
+    /// Zero every register of Z, e.g. before a fresh accumulation with
+    /// [`AmxOps::matrix_mul_add_f16`], which would otherwise add onto
+    /// whatever the last op left in Z. See [`AmxOps::clear`].
+    fn clear_z(&self) {
+        self.clear(RegSet::Z)
+    }
+
+    /// Read 512 bytes from regset X/Y. See [`bus::get_matrix_512`].
+    fn get_matrix_512(&self, set: RegSet) -> [u8; 512];
+
+    /// Read all 4096 bytes from regset Z. See [`bus::get_matrix_4096`].
+    fn get_matrix_4096(&self) -> [u8; 4096];
+
+    /// Read regset X/Y as scalars of type `T`, in register order, i.e.
+    /// 256 halfs or 128 floats. See [`AmxOps::get_matrix_512`].
+    fn get_matrix_512_as<T: AmxScalar>(&self, set: RegSet) -> Vec<T>
+    where
+        Self: Sized,
+    {
+        decode(&self.get_matrix_512(set))
+    }
+
+    /// Read regset Z as scalars of type `T`, in register order, i.e.
+    /// 2048 halfs or 1024 floats. See [`AmxOps::get_matrix_4096`].
+    fn get_matrix_4096_as<T: AmxScalar>(&self) -> Vec<T>
+    where
+        Self: Sized,
+    {
+        decode(&self.get_matrix_4096())
+    }
+
+    /// Write one register's worth of scalars to Z row `index`, which
+    /// is interleaved with its neighbour. See [`AmxOps::ldzi`].
+    fn load_z_interleaved<T: AmxScalar>(&self, index: u8, data: &[T])
+    where
+        Self: Sized,
+    {
+        assert!(index < 64, "Z has 64 rows, so index {} is out of range", index);
+        assert_eq!(data.len(), T::TILE, "a register holds exactly {} scalars", T::TILE);
+
+        let mut buf = [0u8; 64];
+        let size = mem::size_of::<T>();
+        data.iter()
+            .enumerate()
+            .for_each(|(i, &v)| T::put(&mut buf, i * size, v));
+
+        self.ldzi(index, &buf);
+    }
+
+    /// Read one register's worth of scalars from Z row `index`, as they
+    /// were written by [`AmxOps::load_z_interleaved`], into `out`.
+    fn store_z_interleaved<T: AmxScalar>(&self, index: u8, out: &mut [T])
+    where
+        Self: Sized,
+    {
+        assert!(index < 64, "Z has 64 rows, so index {} is out of range", index);
+        assert_eq!(out.len(), T::TILE, "a register holds exactly {} scalars", T::TILE);
+
+        let buf = self.stzi(index);
+        let size = mem::size_of::<T>();
+        out.iter_mut()
+            .enumerate()
+            .for_each(|(i, v)| *v = T::get(&buf, i * size));
+    }
+
+    /// Multiplies register `x` of X by register `y` of Y as the type
+    /// given by `ty`, writing or adding their outer product to `z`. One
+    /// op reads one register of each, so a product over all 8 pairs of
+    /// registers takes 8 ops, each adding onto the last.
+    fn matrix_mul_regs(&self, ty: MulType, x: u8, y: u8);
+
+    /// Multiplies the first register of X by the first of Y, as the
+    /// type given by `ty`. See [`AmxOps::matrix_mul_regs`], which this
+    /// and every typed op below delegates to.
+    fn matrix_mul(&self, ty: MulType) {
+        self.matrix_mul_regs(ty, 0, 0)
+    }
+
+    /// Multiplies register 0 of X by that of Y as float16, writing over `z`.
+    fn matrix_mul_f16(&self) {
+        self.matrix_mul(MulType::F16)
+    }
+
+    /// Multiplies register 0 of X by that of Y as int16, writing over `z`.
+    /// This wraps on overflow. See [`bus::matrix_mul_add_i16`].
+    fn matrix_mul_i16(&self) {
+        self.matrix_mul(MulType::I16)
+    }
+
+    /// Multiplies register 0 of X by that of Y as float16, adding to `z`.
+    fn matrix_mul_add_f16(&self) {
+        self.matrix_mul(MulType::F16Add)
+    }
+
+    /// Multiplies register 0 of X by that of Y as int16, adding to `z`.
+    /// `z` is int16 too, so this wraps on overflow, e.g. in any deep
+    /// reduction. See [`bus::matrix_mul_add_i16`] for the accumulator.
+    fn matrix_mul_add_i16(&self) {
+        self.matrix_mul(MulType::I16Add)
+    }
+
+    /// Multiplies register 0 of X by that of Y as float32, writing over `z`.
+    /// This is a 16x16 product. See [`bus::matrix_mul_f32`] for more.
+    fn matrix_mul_f32(&self) {
+        self.matrix_mul(MulType::F32)
+    }
+
+    /// Multiplies register 0 of X by that of Y as float32, adding to `z`.
+    /// This is a 16x16 product. See [`bus::matrix_mul_f32`] for more.
+    fn matrix_mul_add_f32(&self) {
+        self.matrix_mul(MulType::F32Add)
+    }
+}
+
+/// Reinterpret the bytes read from a register set as scalars of type
+/// `T`, laid out as AMX lays them out, without any transmutes.
+fn decode<T: AmxScalar>(buf: &[u8]) -> Vec<T> {
+    let size = mem::size_of::<T>();
+    debug_assert!(
+        buf.len().is_multiple_of(size),
+        "{} bytes is not a whole number of scalars",
+        buf.len()
+    );
+
+    (0..buf.len() / size)
+        .map(|i| T::get(buf, i * size))
+        .collect()
+}
+
+/// Panic unless `reg` is one of the registers of `set`, since the ops
+/// encode it in fewer bits than a `u8`, so it'd silently wrap around.
+fn check_reg(set: RegSet, reg: u8) {
+    assert!(
+        reg < set.regs(),
+        "{:?} has {} registers, so {} is out of range",
+        set,
+        set.regs(),
+        reg
+    );
+}
+
+impl AmxOps for AmxCtx {
+    fn ldx(&self, reg: u8, data: &[u8; 64]) {
+        check_reg(RegSet::X, reg);
+        self.mark_loaded(RegSet::X, 1 << reg);
+        bus::set_vector(RegSet::X, reg as u64, data as *const [u8])
+    }
+
+    fn ldy(&self, reg: u8, data: &[u8; 64]) {
+        check_reg(RegSet::Y, reg);
+        self.mark_loaded(RegSet::Y, 1 << reg);
+        bus::set_vector(RegSet::Y, reg as u64, data as *const [u8])
+    }
+
+    fn ldz(&self, reg: u8, data: &[u8; 64]) {
+        check_reg(RegSet::Z, reg);
+        self.mark_loaded(RegSet::Z, 1 << reg);
+        bus::set_vector(RegSet::Z, reg as u64, data as *const [u8])
+    }
+
+    fn ldzi(&self, reg: u8, data: &[u8; 64]) {
+        check_reg(RegSet::Z, reg);
+        // This writes half of each of the pair of rows `reg` is in.
+        self.mark_loaded(RegSet::Z, 0b11 << (reg & !1));
+        bus::set_vector_interleaved(reg as u64, data as *const [u8])
+    }
+
+    fn stx(&self, reg: u8) -> [u8; 64] {
+        check_reg(RegSet::X, reg);
+        bus::get_vector(RegSet::X, reg as u64)
+    }
+
+    fn sty(&self, reg: u8) -> [u8; 64] {
+        check_reg(RegSet::Y, reg);
+        bus::get_vector(RegSet::Y, reg as u64)
+    }
+
+    fn stz(&self, reg: u8) -> [u8; 64] {
+        check_reg(RegSet::Z, reg);
+        bus::get_vector(RegSet::Z, reg as u64)
+    }
+
+    fn stzi(&self, reg: u8) -> [u8; 64] {
+        check_reg(RegSet::Z, reg);
+        bus::get_vector_interleaved(reg as u64)
+    }
+
+    fn set_matrix(&self, set: RegSet, data: &[u8]) {
+        self.mark_loaded(set, u64::MAX >> (64 - set.regs()));
+        bus::set_matrix(set, data)
+    }
+
+    fn get_matrix_512(&self, set: RegSet) -> [u8; 512] {
+        bus::get_matrix_512(set)
+    }
+
+    fn get_matrix_4096(&self) -> [u8; 4096] {
+        bus::get_matrix_4096()
+    }
+
+    fn matrix_mul_regs(&self, ty: MulType, x: u8, y: u8) {
+        check_reg(RegSet::X, x);
+        check_reg(RegSet::Y, y);
+        let (x, y) = (x as u64, y as u64);
+        match ty {
+            MulType::F16 => bus::matrix_mul_f16(x, y),
+            MulType::I16 => bus::matrix_mul_i16(x, y),
+            MulType::F32 => bus::matrix_mul_f32(x, y),
+            MulType::F16Add => bus::matrix_mul_add_f16(x, y),
+            MulType::I16Add => bus::matrix_mul_add_i16(x, y),
+            MulType::F32Add => bus::matrix_mul_add_f32(x, y),
+        }
+    }
+}
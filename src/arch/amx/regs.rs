@@ -1,4 +1,4 @@
-use super::bus;
+use super::ops::AmxOps;
 
 /// A register set exposed by AMX, i.e. a matrix:
 ///
@@ -8,8 +8,9 @@ use super::bus;
 ///
 /// These are generally addressed by register when storing (reading)
 /// or loading (writing) data, but are generally addressed as whole
-/// matrices when operating on mathematically.
-#[derive(Clone, Copy, Eq, PartialEq)]
+/// matrices when operating on mathematically. For the former, see
+/// [`XVec`], [`YVec`] and [`ZVec`], which name a single register.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 #[repr(u8)]
 pub enum RegSet {
     X,
@@ -18,82 +19,72 @@ pub enum RegSet {
 }
 
 impl RegSet {
-    const fn from_u8(u: u8) -> Self {
-        match u {
-            0 => Self::X,
-            1 => Self::Y,
-            2 => Self::Z,
-            x => panic!("value {} not representable as RegSet", x),
+    /// The number of 64-byte registers in this set.
+    pub const fn regs(self) -> u8 {
+        match self {
+            RegSet::X | RegSet::Y => 8,
+            RegSet::Z => 64,
         }
     }
 }
 
-pub struct XRegs;
-impl Reg64x8<0> for ZRegs {}
+/// Define a newtype naming one register of a set, which can only be
+/// constructed in range, so that loads & stores through it can't fail.
+macro_rules! reg {
+    ($($(#[$doc:meta])* $name:ident: $set:ident, $ld:ident, $st:ident);* $(;)?) => {
+        $(
+            $(#[$doc])*
+            #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+            pub struct $name(u8);
 
-pub struct F16X;
-impl F16Ops<0> for F16X {}
+            impl $name {
+                #[doc = concat!("The set this names a register of, i.e. [`RegSet::", stringify!($set), "`].")]
+                pub const SET: RegSet = RegSet::$set;
 
-pub struct YRegs;
-impl Reg64x8<1> for ZRegs {}
+                /// Name register `index` of this set, or `None` if there
+                /// isn't one, i.e. it's not less than [`RegSet::regs`].
+                pub const fn new(index: u8) -> Option<Self> {
+                    if index < Self::SET.regs() { Some(Self(index)) } else { None }
+                }
 
-trait Reg64x8<const R: u8> {
-    /// Returns the matrix contents of this register set, as a 64x8
-    /// 2D byte array. See [`super::ops::get_matrix_512`] for more.
-    fn get_matrix(data: &[u8]) -> [u8; 512] {
-        bus::get_matrix_512(const { RegSet::from_u8(R) })
-    }
+                /// The index of this register within its set.
+                pub const fn index(self) -> u8 {
+                    self.0
+                }
 
-    /// Set the contents of this register set as a 64x8 matrix, from
-    /// a 2D byte array. See [`super::ops::set_matrix`] for more.
-    fn set_matrix(data: &[u8]) {
-        bus::set_matrix(const { RegSet::from_u8(R) }, data);
-    }
-}
+                #[doc = concat!("Write 64 bytes to this register. See [`AmxOps::", stringify!($ld), "`].")]
+                pub fn load(self, amx: &(impl AmxOps + ?Sized), data: &[u8; 64]) {
+                    amx.$ld(self.0, data)
+                }
 
-trait F16Ops<const R: u8> {
-    /// Multiply this register by a given vector register `y`, treating
-    /// both as 8x 16bit float vectors. The result is written to `x`.
-    fn vec_mul_in_place() {
-        let R2: u8 = const {
-            match R {
-                1 => 2,
-                2 => 1,
+                #[doc = concat!("Read 64 bytes from this register. See [`AmxOps::", stringify!($st), "`].")]
+                pub fn store(self, amx: &(impl AmxOps + ?Sized)) -> [u8; 64] {
+                    amx.$st(self.0)
+                }
             }
-        };
-
-        bus::matrix_mul_f16()
-    }
+        )*
+    };
 }
 
-trait I16Ops<const R: u8> {
-    /// Multiply this register by a given vector register `y`, with
-    /// the result stored in
-    fn vec_mul_in_place() {
-        let R2: u8 = const {
-            match R {
-                1 => 2,
-                2 => 1,
-            }
-        };
-
-        bus::matrix_mul_i16()
-    }
+reg! {
+    /// One of the 8 registers of X, i.e. a row of the 8x64 matrix.
+    XVec: X, ldx, stx;
+    /// One of the 8 registers of Y, i.e. a row of the 8x64 matrix.
+    YVec: Y, ldy, sty;
+    /// One of the 64 registers of Z, i.e. a row of the 64x64 matrix.
+    ZVec: Z, ldz, stz;
 }
 
-pub struct ZRegs();
-impl Reg64x64<2> for ZRegs {}
-
-trait Reg64x64<const R: u8> {
-    /// Returns the matrix contents of this register set, as a 64x64
-    /// 2D byte array. See [`super::ops::get_matrix_4096`] for more.
-    fn get_matrix(data: &[u8]) -> [u8; 4096] {
-        bus::get_matrix_4096()
+impl ZVec {
+    /// Write 64 bytes to this register, interleaved with its neighbour.
+    /// See [`AmxOps::ldzi`].
+    pub fn load_interleaved(self, amx: &(impl AmxOps + ?Sized), data: &[u8; 64]) {
+        amx.ldzi(self.0, data)
     }
 
-    /// Set the contents of this register set as a 64x64 matrix, from
-    /// a 2D byte array. See [`super::ops::set_matrix`] for more.
-    fn set_matrix(data: &[u8]) {
-        bus::set_matrix(const { RegSet::from_u8(R) }, data);
+    /// Read 64 bytes from this register, as they were written by
+    /// [`ZVec::load_interleaved`]. See [`AmxOps::stzi`].
+    pub fn store_interleaved(self, amx: &(impl AmxOps + ?Sized)) -> [u8; 64] {
+        amx.stzi(self.0)
     }
 }
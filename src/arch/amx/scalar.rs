@@ -0,0 +1,165 @@
+//! This module defines how each scalar type which AMX can operate
+//! on is laid out in its registers, and which ops multiply it.
+
+use core::{mem, ops};
+
+use super::{AmxOps, MulType};
+use crate::arch::{scalar, Backend, Kernel};
+
+/// The number of registers in each of X and Y. Each op multiplies just
+/// one register of X by one of Y, chosen by their offsets, into an
+/// outer product in Z, so a block this deep takes one op per register.
+pub const DEPTH: usize = 8;
+
+/// A scalar type which the AMX coprocessor can multiply matrices of.
+/// This is only implemented for the types which have an AMX op.
+pub trait AmxScalar: Copy + Default + ops::Add<Output = Self> + ops::Mul<Output = Self> {
+    /// The side length of the square block of the product which fits
+    /// in Z. This is the number of scalars in one 64-byte register.
+    const TILE: usize = 64 / mem::size_of::<Self>();
+
+    /// Write a scalar into `buf` at byte offset `off`, as AMX expects.
+    fn put(buf: &mut [u8], off: usize, v: Self);
+
+    /// Read a scalar from `buf` at byte offset `off`, as AMX writes it.
+    fn get(buf: &[u8], off: usize) -> Self;
+
+    /// Multiply register `reg` of X by register `reg` of Y, writing
+    /// their outer product to Z.
+    fn matrix_mul(amx: &impl AmxOps, reg: u8);
+
+    /// Multiply register `reg` of X by register `reg` of Y, adding
+    /// their outer product to Z.
+    fn matrix_mul_add(amx: &impl AmxOps, reg: u8);
+
+    /// The kernel which multiplies matrices of this type on `backend`,
+    /// or `None` if it can't, either on this target or for this type.
+    /// By default, only [`Backend::Scalar`] can. AMX isn't a kernel, so
+    /// is never returned: it's used directly, via [`AmxOps`].
+    fn kernel(backend: Backend) -> Option<Kernel<Self>> {
+        (backend == Backend::Scalar).then_some(scalar::matrix_mul as Kernel<Self>)
+    }
+
+    /// Multiply `a` (MxK) by `b` (KxN) into `c` (MxN), all column-major,
+    /// without AMX. This is what we fall back to if it's unavailable:
+    /// the first [`AmxScalar::kernel`] of NEON, AVX2 and scalar code.
+    fn matrix_mul_fallback(a: &[Self], b: &[Self], c: &mut [Self], m: usize, k: usize, n: usize) {
+        let kernel = [Backend::Neon, Backend::Avx2]
+            .into_iter()
+            .find_map(Self::kernel)
+            .unwrap_or(scalar::matrix_mul);
+        kernel(a, b, c, m, k, n)
+    }
+
+    /// The kernel which multiplies matrices of this type on `backend`,
+    /// adding the product to `c`, rather than overwriting it, or `None`
+    /// if it can't. See [`AmxScalar::kernel`].
+    fn kernel_add(backend: Backend) -> Option<Kernel<Self>> {
+        (backend == Backend::Scalar).then_some(scalar::matrix_mul_add as Kernel<Self>)
+    }
+
+    /// Multiply `a` (MxK) by `b` (KxN), adding the product to `c` (MxN),
+    /// all column-major, without AMX, on the first backend that can, as
+    /// [`AmxScalar::matrix_mul_fallback`] does.
+    fn matrix_mul_add_fallback(a: &[Self], b: &[Self], c: &mut [Self], m: usize, k: usize, n: usize) {
+        let kernel = [Backend::Neon, Backend::Avx2]
+            .into_iter()
+            .find_map(Self::kernel_add)
+            .unwrap_or(scalar::matrix_mul_add);
+        kernel(a, b, c, m, k, n)
+    }
+}
+
+impl AmxScalar for f16 {
+    fn put(buf: &mut [u8], off: usize, v: Self) {
+        buf[off..off + 2].copy_from_slice(&v.to_le_bytes());
+    }
+
+    fn get(buf: &[u8], off: usize) -> Self {
+        f16::from_le_bytes([buf[off], buf[off + 1]])
+    }
+
+    fn matrix_mul(amx: &impl AmxOps, reg: u8) {
+        amx.matrix_mul_regs(MulType::F16, reg, reg)
+    }
+
+    fn matrix_mul_add(amx: &impl AmxOps, reg: u8) {
+        amx.matrix_mul_regs(MulType::F16Add, reg, reg)
+    }
+
+    fn kernel(backend: Backend) -> Option<Kernel<Self>> {
+        match backend {
+            #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+            Backend::Neon => Some(crate::arch::neon::matrix_mul_f16),
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 if crate::arch::x86::has_avx2_fma() => Some(crate::arch::x86::matrix_mul_f16),
+            Backend::Scalar => Some(scalar::matrix_mul),
+            _ => None,
+        }
+    }
+
+    fn kernel_add(backend: Backend) -> Option<Kernel<Self>> {
+        match backend {
+            #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+            Backend::Neon => Some(crate::arch::neon::matrix_mul_add_f16),
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 if crate::arch::x86::has_avx2_fma() => Some(crate::arch::x86::matrix_mul_add_f16),
+            Backend::Scalar => Some(scalar::matrix_mul_add),
+            _ => None,
+        }
+    }
+}
+
+impl AmxScalar for f32 {
+    fn put(buf: &mut [u8], off: usize, v: Self) {
+        buf[off..off + 4].copy_from_slice(&v.to_le_bytes());
+    }
+
+    fn get(buf: &[u8], off: usize) -> Self {
+        f32::from_le_bytes([buf[off], buf[off + 1], buf[off + 2], buf[off + 3]])
+    }
+
+    fn matrix_mul(amx: &impl AmxOps, reg: u8) {
+        amx.matrix_mul_regs(MulType::F32, reg, reg)
+    }
+
+    fn matrix_mul_add(amx: &impl AmxOps, reg: u8) {
+        amx.matrix_mul_regs(MulType::F32Add, reg, reg)
+    }
+
+    fn kernel(backend: Backend) -> Option<Kernel<Self>> {
+        match backend {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 if crate::arch::x86::has_avx2_fma() => Some(crate::arch::x86::matrix_mul_f32),
+            Backend::Scalar => Some(scalar::matrix_mul),
+            _ => None,
+        }
+    }
+
+    fn kernel_add(backend: Backend) -> Option<Kernel<Self>> {
+        match backend {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 if crate::arch::x86::has_avx2_fma() => Some(crate::arch::x86::matrix_mul_add_f32),
+            Backend::Scalar => Some(scalar::matrix_mul_add),
+            _ => None,
+        }
+    }
+}
+
+impl AmxScalar for i16 {
+    fn put(buf: &mut [u8], off: usize, v: Self) {
+        buf[off..off + 2].copy_from_slice(&v.to_le_bytes());
+    }
+
+    fn get(buf: &[u8], off: usize) -> Self {
+        i16::from_le_bytes([buf[off], buf[off + 1]])
+    }
+
+    fn matrix_mul(amx: &impl AmxOps, reg: u8) {
+        amx.matrix_mul_regs(MulType::I16, reg, reg)
+    }
+
+    fn matrix_mul_add(amx: &impl AmxOps, reg: u8) {
+        amx.matrix_mul_regs(MulType::I16Add, reg, reg)
+    }
+}
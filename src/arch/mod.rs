@@ -1 +1,64 @@
+//! Backends for the vector processing extensions of each target we
+//! support. These are low-level: most users want [`crate::alg`].
+
 pub mod amx;
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))] pub mod neon;
+pub mod scalar;
+#[cfg(target_arch = "x86_64")] pub mod x86;
+
+/// A function which multiplies an MxK matrix by a KxN matrix, both
+/// column-major, into an MxN matrix, i.e. `(a, b, c, m, k, n)`. This
+/// is the shape of every backend's matrix multiply, bar AMX's own. A
+/// `matrix_mul` kernel overwrites `c`, and a `matrix_mul_add` adds to it.
+pub type Kernel<T> = fn(&[T], &[T], &mut [T], usize, usize, usize);
+
+/// A backend which can multiply matrices, i.e. one of the modules of
+/// [`crate::arch`]. Which ones are available depends on the target,
+/// and on the scalar type, e.g. NEON can only multiply `f16`s.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Backend {
+    /// Apple's AMX coprocessor, on Apple Silicon Macs. See [`amx`].
+    Amx,
+    /// ARM's NEON vector extension, on other aarch64 chips. See `neon`.
+    Neon,
+    /// Intel's AVX2 and FMA vector extensions, on x86-64. See `x86`.
+    Avx2,
+    /// No vector instructions at all, on any target. See [`scalar`].
+    Scalar,
+}
+
+/// What this crate can accelerate on the machine it's running on, as
+/// returned by [`capabilities`]. Each field says whether a backend is
+/// available at all, not whether a given scalar type can use it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// Apple's AMX coprocessor. See [`Backend::Amx`].
+    pub amx: bool,
+    /// ARM's NEON vector extension. See [`Backend::Neon`].
+    pub neon: bool,
+    /// Intel's AVX2 and FMA vector extensions. See [`Backend::Avx2`].
+    pub avx2: bool,
+    /// Intel's AVX-512 vector extension, which no backend uses yet.
+    pub avx512: bool,
+}
+
+/// Returns what this crate can accelerate on this machine, e.g. for
+/// logging which backend it'll use. AMX has no way to ask the CPU for
+/// it, besides enabling it, so it's inferred from the target: every
+/// Apple Silicon Mac has it. NEON is part of every aarch64 target we
+/// build for, while x86-64 chips are asked at runtime, via `cpuid`.
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        amx: cfg!(all(target_arch = "aarch64", target_os = "macos", target_pointer_width = "64")),
+        neon: cfg!(all(target_arch = "aarch64", target_feature = "neon")),
+        #[cfg(target_arch = "x86_64")]
+        avx2: x86::has_avx2_fma(),
+        #[cfg(not(target_arch = "x86_64"))]
+        avx2: false,
+        #[cfg(target_arch = "x86_64")]
+        avx512: x86::has_avx512f(),
+        #[cfg(not(target_arch = "x86_64"))]
+        avx512: false,
+    }
+}
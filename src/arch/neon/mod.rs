@@ -0,0 +1,63 @@
+//! This module is a fallback for ARM chips which don't have AMX, but
+//! do have NEON, e.g. AWS Graviton or the Raspberry Pi. NEON's lanes
+//! are only 128 bits wide, and not every NEON chip supports half-width
+//! float arithmetic, so we widen to f32 and use `fmla` on 4 lanes.
+
+use alloc::vec;
+use core::arch::aarch64::{vdupq_n_f32, vfmaq_f32, vld1q_f32, vst1q_f32};
+
+/// The number of f32 lanes in a NEON register.
+const LANES: usize = 4;
+
+/// Matrix multiplies `x` (MxK) by `y` (KxN) as float16, on NEON,
+/// writing the MxN product to `z`. All 3 are column-major slices.
+pub(crate) fn matrix_mul_f16(x: &[f16], y: &[f16], z: &mut [f16], m: usize, k: usize, n: usize) {
+    matrix_mul_f16_neon::<false>(x, y, z, m, k, n)
+}
+
+/// Matrix multiplies `x` (MxK) by `y` (KxN) as float16, on NEON,
+/// adding the MxN product to `z`, i.e. `z += x * y`.
+pub(crate) fn matrix_mul_add_f16(x: &[f16], y: &[f16], z: &mut [f16], m: usize, k: usize, n: usize) {
+    matrix_mul_f16_neon::<true>(x, y, z, m, k, n)
+}
+
+/// The kernel behind [`matrix_mul_f16`] and [`matrix_mul_add_f16`].
+/// If `ACC`, each sum starts from `z`, rather than from zero.
+fn matrix_mul_f16_neon<const ACC: bool>(x: &[f16], y: &[f16], z: &mut [f16], m: usize, k: usize, n: usize) {
+    debug_assert!(x.len() == m * k, "x must be {}x{} but had {} elements", m, k, x.len());
+    debug_assert!(y.len() == k * n, "y must be {}x{} but had {} elements", k, n, y.len());
+    debug_assert!(z.len() == m * n, "z must be {}x{} but had {} elements", m, n, z.len());
+
+    // Widen `x` to f32 up front, padding each column to a whole number
+    // of lanes, so the inner loop never has to handle a partial chunk.
+    let m_pad = m.div_ceil(LANES) * LANES;
+    let mut xw = vec![0f32; m_pad * k];
+    (0..k).for_each(|p| {
+        (0..m).for_each(|i| xw[p * m_pad + i] = x[p * m + i] as f32);
+    });
+
+    // Each column of `z` is a sum of the columns of `x`, weighted by
+    // the corresponding column of `y`: `z[.., j] = sum(x[.., p] * y[p, j])`.
+    (0..n).for_each(|j| {
+        (0..m_pad).step_by(LANES).for_each(|i| {
+            let mut buf = [0f32; LANES];
+            if ACC {
+                (i..m.min(i + LANES)).for_each(|r| buf[r - i] = z[j * m + r] as f32);
+            }
+
+            // Safe: `xw` has `m_pad * k` elements, and `i + LANES <= m_pad`,
+            // so each load of `LANES` f32s from `xw[p * m_pad + i]` is in
+            // bounds. `buf` is exactly `LANES` f32s.
+            unsafe {
+                let acc = (0..k).fold(vld1q_f32(buf.as_ptr()), |acc, p| {
+                    let w = vdupq_n_f32(y[j * k + p] as f32);
+                    vfmaq_f32(acc, vld1q_f32(xw.as_ptr().add(p * m_pad + i)), w)
+                });
+                vst1q_f32(buf.as_mut_ptr(), acc);
+            }
+
+            // Write back only the rows that exist, skipping the padding.
+            (i..m.min(i + LANES)).for_each(|r| z[j * m + r] = buf[r - i] as f16);
+        });
+    });
+}
@@ -0,0 +1,36 @@
+//! This module is the last resort, for targets with neither AMX nor
+//! NEON, or types neither supports. It uses no vector instructions at
+//! all, and is written to be obviously correct rather than fast, so
+//! that it can serve as the reference to check the other backends by.
+
+use core::ops;
+
+/// Matrix multiplies `x` (MxK) by `y` (KxN), writing the MxN product
+/// to `z`, whatever was in it. All 3 are column-major slices.
+pub(crate) fn matrix_mul<T>(x: &[T], y: &[T], z: &mut [T], m: usize, k: usize, n: usize)
+where
+    T: ops::Add<Output = T> + ops::Mul<Output = T> + Copy + Default,
+{
+    z.fill(T::default());
+    matrix_mul_add(x, y, z, m, k, n)
+}
+
+/// Matrix multiplies `x` (MxK) by `y` (KxN), adding the MxN product
+/// to `z`, i.e. `z += x * y`. All 3 are column-major slices.
+pub(crate) fn matrix_mul_add<T>(x: &[T], y: &[T], z: &mut [T], m: usize, k: usize, n: usize)
+where
+    T: ops::Add<Output = T> + ops::Mul<Output = T> + Copy,
+{
+    debug_assert!(x.len() == m * k, "x must be {}x{} but had {} elements", m, k, x.len());
+    debug_assert!(y.len() == k * n, "y must be {}x{} but had {} elements", k, n, y.len());
+    debug_assert!(z.len() == m * n, "z must be {}x{} but had {} elements", m, n, z.len());
+
+    // The textbook triple loop, ordered so the innermost loop runs down
+    // a column of `x` and of `z`, both of which are contiguous.
+    (0..n).for_each(|j| {
+        (0..k).for_each(|p| {
+            let w = y[j * k + p];
+            (0..m).for_each(|i| z[j * m + i] = z[j * m + i] + x[p * m + i] * w);
+        });
+    });
+}
@@ -0,0 +1,172 @@
+//! This module is a fallback for x86-64 chips, i.e. Intel and AMD,
+//! which have AVX2 and FMA, as every one since Haswell (2013) does.
+//! Since that's not every x86-64 chip, and `no_std` has no way to
+//! ask the OS, we check the CPU's support at runtime via `cpuid`,
+//! and fall back to [`crate::arch::scalar`] if it's not there.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::arch::x86_64::{
+    __cpuid, __cpuid_count, _mm256_fmadd_ps, _mm256_loadu_ps, _mm256_set1_ps, _mm256_setzero_ps,
+    _mm256_storeu_ps, _xgetbv,
+};
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::arch::scalar;
+
+/// The number of f32 lanes in an AVX register.
+const LANES: usize = 8;
+
+/// Whether AVX2 and FMA are available: 0 if we haven't checked yet,
+/// 1 if they aren't, and 2 if they are. This can't change while the
+/// program runs, so we only need to check once, and races are benign.
+static AVX2_FMA: AtomicU8 = AtomicU8::new(0);
+
+/// Whether this CPU supports AVX2 and FMA, and the OS has enabled the
+/// 256-bit registers they use, i.e. whether [`matrix_mul_f32`] can use
+/// them. This is what `is_x86_feature_detected!` does, minus `std`.
+pub fn has_avx2_fma() -> bool {
+    match AVX2_FMA.load(Ordering::Relaxed) {
+        0 => {
+            let has = detect_avx2_fma();
+            AVX2_FMA.store(1 + has as u8, Ordering::Relaxed);
+            has
+        }
+        v => v == 2,
+    }
+}
+
+/// Ask the CPU (via `cpuid`) and the OS (via `xgetbv`) for AVX2 & FMA.
+fn detect_avx2_fma() -> bool {
+    // Leaf 7 is only valid if leaf 0 says it is, so check that first.
+    let (leaf1, max) = (__cpuid(1), __cpuid(0).eax);
+    let (fma, osxsave, avx) = (leaf1.ecx & 1 << 12 != 0, leaf1.ecx & 1 << 27 != 0, leaf1.ecx & 1 << 28 != 0);
+    if !(fma && osxsave && avx) || max < 7 {
+        return false;
+    }
+
+    // Safe: `osxsave` says `xgetbv` is available. Bits 1 and 2 of XCR0
+    // say the OS saves the SSE and AVX registers, without which it'd be
+    // unsafe to use them across context switches.
+    let (leaf7, xcr0) = (__cpuid_count(7, 0), unsafe { xgetbv() });
+    leaf7.ebx & 1 << 5 != 0 && xcr0 & 0b110 == 0b110
+}
+
+/// Whether this CPU supports AVX-512 (i.e. its foundation, AVX512F),
+/// and the OS has enabled the 512-bit registers it uses. Nothing here
+/// uses it yet, bar [`crate::arch::capabilities`], so it isn't cached.
+pub fn has_avx512f() -> bool {
+    let (leaf1, max) = (__cpuid(1), __cpuid(0).eax);
+    if leaf1.ecx & 1 << 27 == 0 || max < 7 {
+        return false;
+    }
+
+    // Safe: As above. Bits 5-7 of XCR0 say the OS saves the mask & upper
+    // 256 bits of each of the 32 AVX-512 registers, on top of SSE & AVX.
+    let (leaf7, xcr0) = (__cpuid_count(7, 0), unsafe { xgetbv() });
+    leaf7.ebx & 1 << 16 != 0 && xcr0 & 0b1110_0110 == 0b1110_0110
+}
+
+/// Read XCR0. This needs the `xsave` feature to be enabled to compile.
+#[target_feature(enable = "xsave")]
+unsafe fn xgetbv() -> u64 {
+    _xgetbv(0)
+}
+
+/// Matrix multiplies `x` (MxK) by `y` (KxN) as float32, with AVX2 if
+/// the CPU has it, writing the MxN product to `z`. All 3 are column-major.
+pub(crate) fn matrix_mul_f32(x: &[f32], y: &[f32], z: &mut [f32], m: usize, k: usize, n: usize) {
+    matrix_mul_f32_with::<false>(x, y, z, m, k, n)
+}
+
+/// Matrix multiplies `x` (MxK) by `y` (KxN) as float32, with AVX2 if
+/// the CPU has it, adding the MxN product to `z`, i.e. `z += x * y`.
+pub(crate) fn matrix_mul_add_f32(x: &[f32], y: &[f32], z: &mut [f32], m: usize, k: usize, n: usize) {
+    matrix_mul_f32_with::<true>(x, y, z, m, k, n)
+}
+
+/// Matrix multiplies `x` (MxK) by `y` (KxN) as float16, writing the
+/// MxN product to `z`. x86-64 has no half-width float arithmetic, so
+/// this widens to f32, and narrows the product, as NEON does.
+pub(crate) fn matrix_mul_f16(x: &[f16], y: &[f16], z: &mut [f16], m: usize, k: usize, n: usize) {
+    matrix_mul_f16_with::<false>(x, y, z, m, k, n)
+}
+
+/// Matrix multiplies `x` (MxK) by `y` (KxN) as float16, adding the
+/// MxN product to `z`. This widens `z` too, so the sum is in f32.
+pub(crate) fn matrix_mul_add_f16(x: &[f16], y: &[f16], z: &mut [f16], m: usize, k: usize, n: usize) {
+    matrix_mul_f16_with::<true>(x, y, z, m, k, n)
+}
+
+/// Pick the kernel behind [`matrix_mul_f32`] and [`matrix_mul_add_f32`],
+/// i.e. AVX2 if the CPU has it, and scalar code otherwise.
+fn matrix_mul_f32_with<const ACC: bool>(x: &[f32], y: &[f32], z: &mut [f32], m: usize, k: usize, n: usize) {
+    debug_assert!(x.len() == m * k, "x must be {}x{} but had {} elements", m, k, x.len());
+    debug_assert!(y.len() == k * n, "y must be {}x{} but had {} elements", k, n, y.len());
+    debug_assert!(z.len() == m * n, "z must be {}x{} but had {} elements", m, n, z.len());
+
+    if has_avx2_fma() {
+        // Safe: We've just checked that the CPU supports AVX2 and FMA.
+        unsafe { matrix_mul_f32_avx2::<ACC>(x, y, z, m, k, n) }
+    } else if ACC {
+        scalar::matrix_mul_add(x, y, z, m, k, n)
+    } else {
+        scalar::matrix_mul(x, y, z, m, k, n)
+    }
+}
+
+/// The f16 kernel behind [`matrix_mul_f16`] and [`matrix_mul_add_f16`],
+/// which widens to f32, and narrows the product back into `z`.
+fn matrix_mul_f16_with<const ACC: bool>(x: &[f16], y: &[f16], z: &mut [f16], m: usize, k: usize, n: usize) {
+    let widen = |s: &[f16]| s.iter().map(|&v| v as f32).collect::<Vec<_>>();
+    let mut zw = if ACC { widen(z) } else { vec![0f32; m * n] };
+    matrix_mul_f32_with::<ACC>(&widen(x), &widen(y), &mut zw, m, k, n);
+    z.iter_mut()
+        .zip(zw)
+        .for_each(|(z, w)| *z = w as f16);
+}
+
+/// The AVX2 kernel behind [`matrix_mul_f32`]. Each column of `z` is a
+/// sum of the columns of `x`, weighted by the corresponding column of
+/// `y`: `z[.., j] = sum(x[.., p] * y[p, j])`, so we take `LANES` rows
+/// of it at a time, and the last few that don't fill a register alone.
+/// If `ACC`, each sum starts from `z`, rather than from zero.
+///
+/// Unsafe: The CPU must support AVX2 and FMA. See [`has_avx2_fma`].
+#[target_feature(enable = "avx2,fma")]
+unsafe fn matrix_mul_f32_avx2<const ACC: bool>(
+    x: &[f32],
+    y: &[f32],
+    z: &mut [f32],
+    m: usize,
+    k: usize,
+    n: usize,
+) {
+    let full = m / LANES * LANES;
+
+    (0..n).for_each(|j| {
+        (0..full).step_by(LANES).for_each(|i| {
+            // Safe: `x` has `m * k` elements, and `i + LANES <= m`, so each
+            // load of `LANES` f32s from `x[p * m + i]` is in bounds, as are
+            // the load and store at `z[j * m + i]`, since `z` has `m * n`.
+            unsafe {
+                let init = if ACC {
+                    _mm256_loadu_ps(z.as_ptr().add(j * m + i))
+                } else {
+                    _mm256_setzero_ps()
+                };
+                let acc = (0..k).fold(init, |acc, p| {
+                    let w = _mm256_set1_ps(y[j * k + p]);
+                    _mm256_fmadd_ps(_mm256_loadu_ps(x.as_ptr().add(p * m + i)), w, acc)
+                });
+                _mm256_storeu_ps(z.as_mut_ptr().add(j * m + i), acc);
+            }
+        });
+
+        // The rows past the last full register, if any, one at a time.
+        (full..m).for_each(|i| {
+            let init = if ACC { z[j * m + i] } else { 0. };
+            z[j * m + i] = (0..k).fold(init, |acc, p| acc + x[p * m + i] * y[j * k + p]);
+        });
+    });
+}
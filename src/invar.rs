@@ -1,19 +1,295 @@
-use core::{marker, ops};
+use alloc::vec::Vec;
+use core::{f32, f64, marker, mem, ops};
+
+use crate::arch::amx::AmxScalar;
 
 /// A [`Scalar`] is a type on which the basic arithmetic operations
 /// apply. Mathematically it is a field. Technically it is a number
 /// type, in Rust the standard `u`, `i`, and `f` types, though it
 /// will also apply to any additional fields you use or depend on.
-pub(crate) trait Scalar =
-    ops::Add + ops::Sub + ops::Div + ops::Mul<Output = Self> + marker::Copy + marker::Sized;
-
-pub(crate) trait Float {}
-impl Float for f32 {}
-impl Float for f64 {}
-
-pub(crate) trait Int {}
-impl Int for i8 {}
-impl Int for i16 {}
-impl Int for i32 {}
-impl Int for i64 {}
-impl Int for i128 {}
+/// Every op yields the same type, so that they can be chained.
+pub trait Scalar = ops::Add<Output = Self>
+    + ops::Sub<Output = Self>
+    + ops::Div<Output = Self>
+    + ops::Mul<Output = Self>
+    + marker::Copy
+    + marker::Sized;
+
+/// A [`Float`] is one of the primitive floating-point types.
+pub trait Float:
+    ops::Add<Output = Self>
+    + ops::Sub<Output = Self>
+    + ops::Div<Output = Self>
+    + ops::Mul<Output = Self>
+    + marker::Copy
+    + Default
+    + PartialOrd
+{
+    /// The difference between 1 and the next largest value, i.e. the
+    /// precision of this type, for telling when a value is all but 0.
+    const EPSILON: Self;
+
+    /// Convert a count, e.g. of elements, to this type. This may lose
+    /// precision, as `as` does, for counts too large to represent.
+    fn from_usize(n: usize) -> Self;
+
+    /// Convert an `f64` to this type, rounding to the nearest value it
+    /// can represent, as `as` does. This is exact for `f64` itself.
+    fn from_f64(x: f64) -> Self;
+
+    /// Convert this value to an `f64`, which is exact for every type, as
+    /// an `f64` can represent every value of the narrower float types.
+    fn to_f64(self) -> f64;
+
+    /// The absolute value, i.e. this value with its sign made positive.
+    fn abs(self) -> Self;
+
+    /// The square root. This is NaN for any negative value but `-0`.
+    fn sqrt(self) -> Self;
+}
+
+impl Float for f16 {
+    const EPSILON: Self = f16::EPSILON;
+
+    fn from_usize(n: usize) -> Self {
+        n as f16
+    }
+
+    fn from_f64(x: f64) -> Self {
+        x as f16
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn abs(self) -> Self {
+        self.abs()
+    }
+
+    fn sqrt(self) -> Self {
+        self.sqrt()
+    }
+}
+
+impl Float for f32 {
+    const EPSILON: Self = f32::EPSILON;
+
+    fn from_usize(n: usize) -> Self {
+        n as f32
+    }
+
+    fn from_f64(x: f64) -> Self {
+        x as f32
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn abs(self) -> Self {
+        self.abs()
+    }
+
+    fn sqrt(self) -> Self {
+        f32::math::sqrt(self)
+    }
+}
+
+impl Float for f64 {
+    const EPSILON: Self = f64::EPSILON;
+
+    fn from_usize(n: usize) -> Self {
+        n as f64
+    }
+
+    fn from_f64(x: f64) -> Self {
+        x
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn abs(self) -> Self {
+        self.abs()
+    }
+
+    fn sqrt(self) -> Self {
+        f64::math::sqrt(self)
+    }
+}
+
+/// A [`Half`] is the 16-bit float type, i.e. the type which AMX's
+/// `matrix_mul_f16` op and NEON's half-width lanes operate on. It's
+/// a marker for the types which take the fastest path through AMX.
+pub trait Half: Float + AmxScalar {}
+impl Half for f16 {}
+
+/// An [`Int`] is one of the primitive signed integer types. Since they
+/// can overflow, which panics in debug builds but wraps in release, it
+/// has each of their overflow modes, so generic code can pick one that
+/// behaves the same in every build.
+pub trait Int: Copy {
+    /// Add, clamping to the bounds of this type on overflow.
+    fn saturating_add(self, rhs: Self) -> Self;
+
+    /// Add, wrapping around the bounds of this type on overflow.
+    fn wrapping_add(self, rhs: Self) -> Self;
+
+    /// Add, or return `None` on overflow.
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+
+    /// Multiply, clamping to the bounds of this type on overflow.
+    fn saturating_mul(self, rhs: Self) -> Self;
+
+    /// Multiply, wrapping around the bounds of this type on overflow.
+    fn wrapping_mul(self, rhs: Self) -> Self;
+
+    /// Multiply, or return `None` on overflow.
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+}
+
+/// Implement [`Int`] for the primitive signed integer types, which all
+/// have inherent methods of the same names to do the work for us.
+macro_rules! impl_int {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl Int for $t {
+                fn saturating_add(self, rhs: Self) -> Self {
+                    <$t>::saturating_add(self, rhs)
+                }
+
+                fn wrapping_add(self, rhs: Self) -> Self {
+                    <$t>::wrapping_add(self, rhs)
+                }
+
+                fn checked_add(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_add(self, rhs)
+                }
+
+                fn saturating_mul(self, rhs: Self) -> Self {
+                    <$t>::saturating_mul(self, rhs)
+                }
+
+                fn wrapping_mul(self, rhs: Self) -> Self {
+                    <$t>::wrapping_mul(self, rhs)
+                }
+
+                fn checked_mul(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_mul(self, rhs)
+                }
+            }
+        )*
+    };
+}
+
+impl_int!(i8, i16, i32, i64, i128);
+
+/// A [`Zero`] is a type with an additive identity, i.e. `x + 0 = x`.
+pub trait Zero {
+    /// The additive identity, i.e. `0`.
+    fn zero() -> Self;
+}
+
+/// A [`One`] is a type with a multiplicative identity, i.e. `x * 1 = x`.
+pub trait One {
+    /// The multiplicative identity, i.e. `1`.
+    fn one() -> Self;
+}
+
+/// Implement [`Zero`] and [`One`] for the primitive number types, for
+/// which they are just the literals `0` and `1`.
+macro_rules! impl_zero_one {
+    ($($t:ty: $zero:literal, $one:literal);* $(;)?) => {
+        $(
+            impl Zero for $t {
+                fn zero() -> Self {
+                    $zero
+                }
+            }
+
+            impl One for $t {
+                fn one() -> Self {
+                    $one
+                }
+            }
+        )*
+    };
+}
+
+impl_zero_one! {
+    u8: 0, 1; u16: 0, 1; u32: 0, 1; u64: 0, 1; u128: 0, 1; usize: 0, 1;
+    i8: 0, 1; i16: 0, 1; i32: 0, 1; i64: 0, 1; i128: 0, 1; isize: 0, 1;
+    f16: 0.0, 1.0; f32: 0.0, 1.0; f64: 0.0, 1.0;
+}
+
+/// A [`Pod`] is a "plain old data" number type, i.e. one which is just
+/// its bytes, with no invariants, so any bytes of the right length are
+/// a valid value. This is what lets tensors be written out as bytes.
+pub trait Pod: Copy {
+    /// The number of bytes in one value, i.e. its `size_of`.
+    const SIZE: usize;
+
+    /// Append this value to `buf`, in little-endian byte order.
+    fn write_le(self, buf: &mut Vec<u8>);
+
+    /// Read a value from the first [`Pod::SIZE`] bytes of `buf`, which
+    /// are in little-endian byte order. This panics if there are fewer.
+    fn read_le(buf: &[u8]) -> Self;
+}
+
+/// Implement [`Pod`] for the primitive number types, which all have
+/// `to_le_bytes` and `from_le_bytes` methods to do the work for us.
+macro_rules! impl_pod {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl Pod for $t {
+                const SIZE: usize = mem::size_of::<$t>();
+
+                fn write_le(self, buf: &mut Vec<u8>) {
+                    buf.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_le(buf: &[u8]) -> Self {
+                    let mut bytes = [0; mem::size_of::<$t>()];
+                    bytes.copy_from_slice(&buf[..mem::size_of::<$t>()]);
+                    <$t>::from_le_bytes(bytes)
+                }
+            }
+        )*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f16, f32, f64);
+
+/// A [`CastAs`] type can be converted to `U` as the `as` operator does,
+/// which may lose information, e.g. `f32` to `i32` rounds toward zero
+/// and saturates, while `i32` to `u8` keeps only the low byte.
+pub trait CastAs<U>: Copy {
+    /// Convert this value to `U`, exactly as `self as U` does.
+    fn cast_as(self) -> U;
+}
+
+/// Implement [`CastAs`] from each of the primitive number types to each
+/// of the others, and to itself, since `as` is defined for every pair.
+macro_rules! impl_cast_as {
+    ($($t:ty),* $(,)?) => {
+        impl_cast_as!(@from [$($t),*] [$($t),*]);
+    };
+    (@from [$($from:ty),*] $to:tt) => {
+        $(impl_cast_as!(@to $from $to);)*
+    };
+    (@to $from:ty [$($to:ty),*]) => {
+        $(
+            impl CastAs<$to> for $from {
+                #[allow(clippy::unnecessary_cast)]
+                fn cast_as(self) -> $to {
+                    self as $to
+                }
+            }
+        )*
+    };
+}
+
+impl_cast_as!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f16, f32, f64);
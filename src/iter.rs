@@ -1 +1,178 @@
+//! Iterators over tensors. Like the storage of a tensor, these are
+//! column-major: the first dim varies fastest, then the second, etc.
 
+use alloc::vec;
+use core::slice;
+
+use crate::space::{shape, Matrix, Tensor};
+
+/// An iterator over the elements of a [`Tensor`], in storage order.
+pub struct TensorIter<'a, T>(slice::Iter<'a, T>);
+
+impl<'a, T> Iterator for TensorIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for TensorIter<'_, T> {}
+
+impl<'a, T> IntoIterator for &'a Tensor<T> {
+    type IntoIter = TensorIter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T> Tensor<T> {
+    /// Iterate over the elements of this tensor, in storage order. A
+    /// tensor without data yields nothing.
+    pub fn iter(&self) -> TensorIter<'_, T> {
+        TensorIter(self.data().unwrap_or(&[]).iter())
+    }
+
+    /// Iterate over the sub-tensors along the (zero-indexed) dim `d`,
+    /// i.e. for each index along `d`, the elements with that index,
+    /// themselves in storage order. For a matrix, `d = 0` yields rows
+    /// and `d = 1` columns. Panics if `d` is not a dim, i.e. `d >= 8`.
+    pub fn axis_iter(&self, d: usize) -> impl Iterator<Item = impl Iterator<Item = &T>> {
+        let data = self.data().unwrap_or(&[]);
+        let len = if data.is_empty() {
+            0
+        } else {
+            self.len_for(d) as usize
+        };
+
+        // The elements with index `k` along `d` are in runs of `inner`,
+        // one run per `inner * len` elements, i.e. per step along the
+        // dims after `d`. Within each of those, they're the `k`th run.
+        let inner = (0..d)
+            .map(|i| self.len_for(i) as usize)
+            .product::<usize>();
+        (0..len).map(move |k| {
+            data.chunks(inner * len)
+                .flat_map(move |c| &c[k * inner..(k + 1) * inner])
+        })
+    }
+}
+
+/// A block of a [`Matrix`], as yielded by [`Matrix::tiles`], which
+/// knows where in the matrix it came from, so that results computed
+/// from it can be written back there. A tile at the bottom or right
+/// edge may overhang the matrix: its overhang is padded with zeros, so
+/// every tile is the full size, e.g. to load into AMX registers as is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatrixTile<T> {
+    origin: (usize, usize),
+    size: (usize, usize),
+    block: Matrix<T>,
+}
+
+impl<T> MatrixTile<T> {
+    /// The row and column in the matrix of this tile's top-left element.
+    pub fn origin(&self) -> (usize, usize) {
+        self.origin
+    }
+
+    /// The height and width of the part of this tile that's inside the
+    /// matrix, i.e. the full tile size, less any overhang at the edges.
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    /// Borrow the elements of this tile, as a matrix of the full tile
+    /// size, including the zeros padding any overhang.
+    pub fn block(&self) -> &Matrix<T> {
+        &self.block
+    }
+
+    /// Take the elements of this tile, as [`MatrixTile::block`] does.
+    pub fn into_block(self) -> Matrix<T> {
+        self.block
+    }
+}
+
+impl<T: Clone + Default> Matrix<T> {
+    /// Iterate over this matrix in tiles of `tile_h` by `tile_w`, e.g.
+    /// 32x32 to fit AMX's registers, going down each column of tiles in
+    /// turn, as the elements are stored. Tiles which overhang the edges
+    /// are padded with zeros: see [`MatrixTile`]. A matrix without data
+    /// yields nothing. This panics if either tile length is zero, or is
+    /// more than [`u16::MAX`], so that a tile can't be a matrix.
+    pub fn tiles(&self, tile_h: usize, tile_w: usize) -> impl Iterator<Item = MatrixTile<T>> + '_ {
+        assert!(tile_h > 0 && tile_w > 0, "cannot split a matrix into tiles of zero size");
+        assert!(
+            tile_h <= u16::MAX as usize && tile_w <= u16::MAX as usize,
+            "cannot split a matrix into tiles longer than {}",
+            u16::MAX
+        );
+
+        let data = self.0.data().unwrap_or(&[]);
+        let (h, w) = (self.0.vlen(), self.0.hlen());
+        let cols = if data.is_empty() { 0 } else { w.div_ceil(tile_w) };
+        (0..cols).flat_map(move |tj| {
+            (0..h.div_ceil(tile_h)).map(move |ti| {
+                let (row, col) = (ti * tile_h, tj * tile_w);
+                let (th, tw) = (tile_h.min(h - row), tile_w.min(w - col));
+
+                let mut block = vec![T::default(); tile_h * tile_w];
+                (0..tw).for_each(|j| {
+                    let from = (col + j) * h + row;
+                    block[j * tile_h..j * tile_h + th].clone_from_slice(&data[from..from + th]);
+                });
+
+                MatrixTile {
+                    origin: (row, col),
+                    size: (th, tw),
+                    block: Matrix(Tensor { data: Some(block), dims: shape(&[tile_h, tile_w]) }),
+                }
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn tiles_pad_partial_edges() {
+        let m = Matrix::from_flat((1..=40 * 40).map(|x| x as f32).collect(), 40, 40).unwrap();
+        let tiles: Vec<_> = m.tiles(32, 32).collect();
+
+        // Down the first column of tiles, then the second.
+        let expected = [
+            ((0, 0), (32, 32)),
+            ((32, 0), (8, 32)),
+            ((0, 32), (32, 8)),
+            ((32, 32), (8, 8)),
+        ];
+        assert_eq!(tiles.len(), expected.len());
+        for (tile, &(origin, size)) in tiles.iter().zip(&expected) {
+            assert_eq!((tile.origin(), tile.size()), (origin, size));
+            assert_eq!((tile.block().0.vlen(), tile.block().0.hlen()), (32, 32));
+
+            // Inside the matrix, each element is where it was. Outside
+            // it, in the overhang, it's zero.
+            for (j, i) in (0..32).flat_map(|j| (0..32).map(move |i| (j, i))) {
+                let (row, col) = (origin.0 + i, origin.1 + j);
+                let x = if i < size.0 && j < size.1 {
+                    (col * 40 + row + 1) as f32
+                } else {
+                    0.0
+                };
+                assert_eq!(tile.block().0.get(&[i, j]), Some(&x));
+            }
+        }
+    }
+}
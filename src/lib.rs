@@ -18,14 +18,14 @@
 //! We allow `incomplete_features` in order to unblock the unstable
 //! feature `generic_const_exprs` (of which more below).
 #![allow(incomplete_features)]
-//! It does rely on 7 features, 6 for asm & const generics, 1 being
-//! `thread_local` to export that macro from [`core`]. All are perf
-//! or ergonomics wins anyway.
-#![feature(asm)]
-#![feature(asm_const)]
-#![feature(core_intrinsics)]
+//! It does rely on 5 features, 2 for const generics & trait aliases,
+//! 1 being `thread_local` to export that macro from [`core`], 1 being
+//! `f16`, the half-precision float that AMX & NEON operate on, and 1
+//! being `core_float_math`, for float functions like `sqrt` sans std.
+//! All are perf or ergonomics wins anyway.
+#![feature(core_float_math)]
+#![feature(f16)]
 #![feature(generic_const_exprs)]
-#![feature(inline_const)]
 #![feature(thread_local)]
 #![feature(trait_alias)]
 
@@ -34,13 +34,22 @@
 extern crate alloc;
 
 pub mod alg;
+pub mod arch;
 #[cfg(feature = "iter")] pub mod iter;
+pub mod prelude;
 
-mod arch;
 mod invar;
 mod space;
 
 /// Trait impls of mathematical operations over tensors.
 pub use alg::*;
+/// The backends which [`Matrix::multiply_with`] can be asked to use,
+/// and which of them this machine has.
+pub use arch::{capabilities, Backend, Capabilities};
+/// Traits for bounding generic code on the kind of scalar it takes.
+pub use invar::{CastAs, Float, Half, Int, One, Pod, Scalar, Zero};
 /// Algebraic types on which all other logic operates.
-pub use space::{Matrix, Tensor, Vector};
+pub use space::{
+    BroadcastErr, ConcatErr, DeserErr, LenErr, Matrix, NzU16, PermuteErr, RaggedErr, ReshapeErr, SMatrix,
+    ShapeErr, SliceErr, SplitErr, StackErr, Tensor, TensorView, Vector,
+};
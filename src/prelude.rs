@@ -0,0 +1,7 @@
+//! The types and traits that almost every user of this crate needs,
+//! for glob-importing in one go: `use spectral::prelude::*;`.
+
+pub use crate::arch::amx::{with_amx, AmxCtx, AmxErr, AmxOps};
+pub use crate::arch::Backend;
+pub use crate::invar::{Float, Half, Int, One, Scalar, Zero};
+pub use crate::space::{Matrix, NzU16, Tensor, TensorView, Vector};
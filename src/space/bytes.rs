@@ -0,0 +1,98 @@
+//! A dependency-free byte format for tensors, for writing them to disk
+//! or the wire without `serde`. See [`Tensor::to_bytes`] for the format.
+
+use alloc::vec::Vec;
+
+use super::{checked_size, NzU16, Tensor, UNUSED};
+use crate::invar::Pod;
+
+/// An error returned by [`Tensor::from_bytes`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum DeserErr {
+    /// The header is invalid: the rank is more than 8, a dim has length
+    /// 0, the data flag is neither 0 nor 1, or the data the dims call
+    /// for would be more bytes than a `usize` can hold. Or there isn't
+    /// one.
+    Header,
+    /// The buffer doesn't hold as many bytes as the header calls for.
+    /// `expected` is the number it should have held, and `got` the
+    /// number it did hold.
+    SizeMismatch { expected: usize, got: usize },
+}
+
+/// ## Serialisation
+impl<T: Pod> Tensor<T> {
+    /// Write this tensor to a new byte buffer. Every number is written
+    /// little-endian, whatever the target's own byte order, so that the
+    /// format is portable. It's laid out as follows, with no padding or
+    /// alignment between the fields:
+    ///
+    /// - 1 byte: the rank `r`, from 0 to 8. See [`Tensor::rank`].
+    /// - `r` x 2 bytes: the length of each of the first `r` dims.
+    /// - 1 byte: 1 if this tensor has data, or 0 if it has none.
+    /// - If it has data, each element, in column-major order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let rank = self.rank();
+        let len = self
+            .data
+            .as_ref()
+            .map_or(0, |d| d.len() * T::SIZE);
+        let mut buf = Vec::with_capacity(2 + rank * 2 + len);
+
+        buf.push(rank as u8);
+        self.dims[..rank]
+            .iter()
+            .for_each(|d| buf.extend_from_slice(&d.get().to_le_bytes()));
+        buf.push(self.data.is_some() as u8);
+        if let Some(d) = &self.data {
+            d.iter().for_each(|&x| x.write_le(&mut buf));
+        }
+
+        buf
+    }
+
+    /// Read a tensor from a byte buffer, as written by [`Tensor::to_bytes`].
+    /// The buffer must hold exactly one tensor, and nothing after it.
+    pub fn from_bytes(buf: &[u8]) -> Result<Tensor<T>, DeserErr> {
+        let rank = *buf.first().ok_or(DeserErr::Header)? as usize;
+        if rank > 8 || buf.len() < 2 + rank * 2 {
+            return Err(DeserErr::Header);
+        }
+
+        let mut dims = [UNUSED; 8];
+        for (d, len) in dims
+            .iter_mut()
+            .zip(buf[1..1 + rank * 2].chunks_exact(2))
+        {
+            *d = NzU16::new(u16::from_le_bytes([len[0], len[1]])).ok_or(DeserErr::Header)?;
+        }
+
+        // Check the dims even without data, as a tensor of any dims must
+        // be able to count its elements, and so must not overflow.
+        let len = checked_size(&dims)
+            .and_then(|n| n.checked_mul(T::SIZE))
+            .ok_or(DeserErr::Header)?;
+        let (flag, body) = (buf[1 + rank * 2], &buf[2 + rank * 2..]);
+        let expected = match flag {
+            0 => 0,
+            1 => len,
+            _ => return Err(DeserErr::Header),
+        };
+        if body.len() != expected {
+            return Err(DeserErr::SizeMismatch {
+                expected: (2 + rank * 2)
+                    .checked_add(expected)
+                    .ok_or(DeserErr::Header)?,
+                got: buf.len(),
+            });
+        }
+
+        let data = (flag == 1).then(|| {
+            body.chunks_exact(T::SIZE)
+                .map(T::read_le)
+                .collect()
+        });
+        Ok(Tensor { data, dims })
+    }
+}
@@ -1,54 +1,382 @@
+use alloc::string::String;
 use alloc::vec::Vec;
+use core::{fmt, ops};
 
-use super::Tensor;
+use super::{
+    concat, display_elem, display_indices, shape, try_dim, ConcatErr, LenErr, RaggedErr, ShapeErr, StackErr,
+    Tensor, Vector, UNUSED,
+};
+use crate::invar::{One, Zero};
 
-pub struct Matrix<T>(Tensor<T>);
+#[derive(Clone, PartialEq)]
+pub struct Matrix<T>(pub(crate) Tensor<T>);
 
-impl<T> Matrix<T> {
-    /// Create a new [`Matrix`] from a 2D [`Vec`], parsing each slice
-    /// **as a column**. Note: This consumes the vector you pass in.
-    pub fn from_cols(md_arr: Vec<Vec<T>>) -> Self {
+impl<T: fmt::Debug> fmt::Debug for Matrix<T> {
+    /// Prints the inner [`Tensor`], as its [`fmt::Debug`] impl does.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Matrix<T> {
+    /// Prints the matrix row by row, as NumPy does, e.g. `[[1, 2],\n
+    /// [3, 4]]`, with each column right-aligned to its widest element.
+    /// Any row or column too long to print in full is cut down to its
+    /// corners, with `...` in place of the rest.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (h, w) = (self.0.vlen(), self.0.hlen());
+        let Some(data) = self.0.data() else {
+            return write!(f, "[{}x{} matrix without data]", h, w);
+        };
+
+        // Format every element we'll print up front, so that we know
+        // how wide each column is before printing any of them.
+        let (rows, cols) = (display_indices(h), display_indices(w));
+        let cells: Vec<Vec<Option<String>>> = rows
+            .iter()
+            .map(|&i| {
+                cols.iter()
+                    .map(|&j| Some(display_elem(&data[j? * h + i?], f)))
+                    .collect()
+            })
+            .collect();
+        let widths: Vec<usize> = (0..cols.len())
+            .map(|c| {
+                cells
+                    .iter()
+                    .filter_map(|row| row[c].as_ref())
+                    .map(|s| s.chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        f.write_str("[")?;
+        for (r, (row, &i)) in cells.iter().zip(&rows).enumerate() {
+            if r > 0 {
+                f.write_str(",\n ")?;
+            }
+            if i.is_none() {
+                f.write_str("...")?;
+                continue;
+            }
+            f.write_str("[")?;
+            for (c, (cell, &width)) in row.iter().zip(&widths).enumerate() {
+                if c > 0 {
+                    f.write_str(", ")?;
+                }
+                match cell {
+                    Some(s) => write!(f, "{:>width$}", s, width = width)?,
+                    None => f.write_str("...")?,
+                }
+            }
+            f.write_str("]")?;
+        }
+        f.write_str("]")
+    }
+}
+
+impl<T> Matrix<T>
+where
+    T: Zero + One,
+{
+    /// Create the NxN identity matrix, i.e. with 1s on the diagonal and
+    /// 0s everywhere else. Multiplying by it leaves a matrix unchanged.
+    pub fn identity(n: usize) -> Self {
         Matrix(Tensor {
             data: Some(
-                md_arr
-                    .into_iter()
-                    .flat_map(|m| m.into_iter())
+                (0..n * n)
+                    .map(|i| if i % n == i / n { T::one() } else { T::zero() })
                     .collect(),
             ),
-            dims: [
-                md_arr.first().map_or(0, |m| m.len()) as u16,
-                md_arr.len() as u16,
-                0,
-                0,
-                0,
-                0,
-                0,
-                0,
-            ],
+            dims: shape(&[n, n]),
         })
     }
+}
+
+impl<T> Matrix<T>
+where
+    T: Zero + Clone,
+{
+    /// Create the NxN matrix with `d` on the diagonal, i.e. element
+    /// `(i, i)` is `d[i]`, and 0s everywhere else, e.g. for scaling.
+    /// [`Matrix::identity`] is the special case of all 1s.
+    pub fn from_diagonal(d: &[T]) -> Self {
+        let n = d.len();
+        Matrix(Tensor {
+            data: Some(
+                (0..n * n)
+                    .map(|i| {
+                        if i % n == i / n {
+                            d[i / n].clone()
+                        } else {
+                            T::zero()
+                        }
+                    })
+                    .collect(),
+            ),
+            dims: shape(&[n, n]),
+        })
+    }
+}
+
+impl<T> Matrix<T> {
+    /// Create a new [`Matrix`] of `rows` by `cols` from a flat [`Vec`],
+    /// in column-major order, i.e. as it is stored. This takes the data
+    /// as it is, so unlike [`Matrix::from_cols`], it doesn't allocate.
+    pub fn from_flat(data: Vec<T>, rows: usize, cols: usize) -> Result<Self, ShapeErr> {
+        let mut dims = [UNUSED; 8];
+        (dims[0], dims[1]) = (try_dim(rows)?, try_dim(cols)?);
+
+        Ok(Matrix(Tensor::try_from_vec(data, dims)?))
+    }
+
+    /// Create a new [`Matrix`] of `rows` by `cols` from a flat [`Vec`],
+    /// in row-major order. This is read as its transpose, and then
+    /// transposed, so it allocates once, as [`Matrix::transpose`] does.
+    pub fn from_flat_rows(data: Vec<T>, rows: usize, cols: usize) -> Result<Self, ShapeErr> {
+        Ok(Matrix::from_flat(data, cols, rows)?.transpose())
+    }
 
     /// Create a new [`Matrix`] from a 2D [`Vec`], parsing each slice
-    /// **as a row**. Note: This consumes the vector you pass in.
+    /// **as a column**. Note: This consumes the vector you pass in. This
+    /// panics if the columns differ in length, or there are none: see
+    /// [`Matrix::try_from_cols`] for an error instead.
+    pub fn from_cols(md_arr: Vec<Vec<T>>) -> Self {
+        Matrix::try_from_cols(md_arr).unwrap_or_else(|e| ragged_panic(e, "column"))
+    }
+
+    /// Create a new [`Matrix`] from a 2D [`Vec`], parsing each slice
+    /// **as a row**. Note: This consumes the vector you pass in. This
+    /// panics if the rows differ in length, or there are none: see
+    /// [`Matrix::try_from_rows`] for an error instead.
     pub fn from_rows(md_arr: Vec<Vec<T>>) -> Self {
-        Matrix(Tensor {
+        Matrix::try_from_rows(md_arr).unwrap_or_else(|e| ragged_panic(e, "row"))
+    }
+
+    /// Create a new [`Matrix`] from a 2D [`Vec`], parsing each slice
+    /// **as a column**, as [`Matrix::from_cols`] does, but checking that
+    /// every column is as long as the first before building anything.
+    pub fn try_from_cols(md_arr: Vec<Vec<T>>) -> Result<Self, RaggedErr> {
+        let (h, w) = check_ragged(&md_arr)?;
+
+        Ok(Matrix(Tensor {
             data: Some(
-                // Loop through 2D array in _column_ order. For each row
-                // index, for each col index, yield the num at the index.
-                (0..md_arr.first().map_or(0, |m| m.len()))
-                    .flat_map(|c| (0..md_arr.len()).map(|r| md_arr[r][c]))
+                md_arr
+                    .into_iter()
+                    .flat_map(|m| m.into_iter())
                     .collect(),
             ),
-            dims: [
-                md_arr.len() as u16,
-                md_arr.first().map_or(0, |m| m.len()) as u16,
-                0,
-                0,
-                0,
-                0,
-                0,
-                0,
-            ],
+            dims: shape(&[h, w]),
+        }))
+    }
+
+    /// Create a new [`Matrix`] from a 2D [`Vec`], parsing each slice
+    /// **as a row**, as [`Matrix::from_rows`] does, but checking that
+    /// every row is as long as the first before building anything, e.g.
+    /// for rows parsed from a file, which may be cut short.
+    pub fn try_from_rows(md_arr: Vec<Vec<T>>) -> Result<Self, RaggedErr> {
+        let (w, h) = check_ragged(&md_arr)?;
+
+        // Loop through 2D array in _column_ order. For each col index,
+        // for each row, yield the next num in that row.
+        let mut rows: Vec<_> = md_arr
+            .into_iter()
+            .map(|m| m.into_iter())
+            .collect();
+        let mut data = Vec::with_capacity(h * w);
+        for _ in 0..w {
+            data.extend(rows.iter_mut().flat_map(|r| r.next()));
+        }
+
+        Ok(Matrix(Tensor { data: Some(data), dims: shape(&[h, w]) }))
+    }
+
+    /// Whether this matrix is square, i.e. has as many rows as columns.
+    pub fn is_square(&self) -> bool {
+        self.0.hlen() == self.0.vlen()
+    }
+
+    /// Transpose this matrix, so an HxW matrix becomes WxH. The data
+    /// is moved into a new buffer in the new column-major order, i.e.
+    /// this allocates once, but doesn't clone any of the elements.
+    pub fn transpose(self) -> Matrix<T> {
+        let (h, w) = (self.0.vlen(), self.0.hlen());
+        let mut dims = self.0.dims;
+        dims.swap(0, 1);
+
+        let data = self.0.data.map(|d| {
+            assert_eq!(d.len(), h * w, "matrix data does not match its {}x{} dims", h, w);
+
+            let mut buf = Vec::with_capacity(h * w);
+            let slots = buf.spare_capacity_mut();
+            // Element (r, c) moves from offset `c * h + r` to `r * w + c`.
+            d.into_iter().enumerate().for_each(|(i, x)| {
+                slots[(i % h) * w + i / h].write(x);
+            });
+
+            // Safe: The map of offsets above is a bijection on `0..h * w`,
+            // so every one of the `h * w` slots has now been written to.
+            unsafe { buf.set_len(h * w) };
+            buf
+        });
+
+        Matrix(Tensor { data, dims })
+    }
+}
+
+/// ## Rows and columns
+impl<T: Clone> Matrix<T> {
+    /// Copy out column `j` as a [`Vector`], or `None` if this matrix
+    /// has no column `j`. This is cheap, as columns are contiguous.
+    pub fn col(&self, j: usize) -> Option<Vector<T>> {
+        let h = self.0.vlen();
+        (j < self.0.hlen()).then(|| {
+            Vector(Tensor {
+                data: self
+                    .0
+                    .data()
+                    .map(|d| d[j * h..(j + 1) * h].to_vec()),
+                dims: shape(&[h]),
+            })
         })
     }
+
+    /// Copy out the diagonal as a [`Vector`], i.e. element `(i, i)` for
+    /// each `i`. For a matrix that isn't square, this is the diagonal
+    /// from the top left, so it's only as long as the shorter side.
+    pub fn diagonal(&self) -> Vector<T> {
+        let (h, n) = (self.0.vlen(), self.0.vlen().min(self.0.hlen()));
+        Vector(Tensor {
+            data: self
+                .0
+                .data()
+                .map(|d| (0..n).map(|i| d[i * h + i].clone()).collect()),
+            dims: shape(&[n]),
+        })
+    }
+
+    /// Copy out row `i` as a [`Vector`], or `None` if this matrix has
+    /// no row `i`. This is a strided copy, as rows are not contiguous.
+    pub fn row(&self, i: usize) -> Option<Vector<T>> {
+        let h = self.0.vlen();
+        (i < h).then(|| {
+            Vector(Tensor {
+                data: self
+                    .0
+                    .data()
+                    .map(|d| d.iter().skip(i).step_by(h).cloned().collect()),
+                dims: shape(&[self.0.hlen()]),
+            })
+        })
+    }
+}
+
+impl<T> ops::Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
+
+    /// Borrow the element at `(row, col)`, i.e. at the column-major
+    /// offset `col * rows + row`. Like indexing a slice, this panics if
+    /// either is out of range, or if this matrix has no data at all.
+    fn index(&self, (row, col): (usize, usize)) -> &T {
+        let (h, w) = (self.0.vlen(), self.0.hlen());
+        self.0
+            .get(&[row, col])
+            .unwrap_or_else(|| index_panic(row, col, h, w))
+    }
+}
+
+impl<T> ops::IndexMut<(usize, usize)> for Matrix<T> {
+    /// Mutably borrow the element at `(row, col)`. This panics in the
+    /// same cases as [`Matrix::index`](ops::Index::index) does.
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut T {
+        let (h, w) = (self.0.vlen(), self.0.hlen());
+        self.0
+            .get_mut(&[row, col])
+            .unwrap_or_else(|| index_panic(row, col, h, w))
+    }
+}
+
+impl<T: Clone> Matrix<T> {
+    /// Join `matrices` side by side, in order, i.e. concatenate them
+    /// along dim 1, as [`Tensor::concat`] does, e.g. to build a block
+    /// matrix. They must all be the same height. If any of them has no
+    /// data, neither does the result.
+    pub fn hstack(matrices: &[Matrix<T>]) -> Result<Matrix<T>, StackErr> {
+        stack(matrices, 1)
+    }
+
+    /// Stack `matrices` one atop another, in order, i.e. concatenate
+    /// them along dim 0, as [`Tensor::concat`] does. They must all be
+    /// the same width. If any of them has no data, neither does the result.
+    pub fn vstack(matrices: &[Matrix<T>]) -> Result<Matrix<T>, StackErr> {
+        stack(matrices, 0)
+    }
+}
+
+/// Concatenate `matrices` along dim `d`, which is 0 or 1, checking the
+/// other dim first, so that a mismatch is reported by height or width.
+fn stack<T: Clone>(matrices: &[Matrix<T>], d: usize) -> Result<Matrix<T>, StackErr> {
+    let first = matrices.first().ok_or(StackErr::Empty)?;
+    let len = |m: &Matrix<T>| if d == 1 { m.0.vlen() } else { m.0.hlen() };
+    if let Some((index, m)) = matrices
+        .iter()
+        .enumerate()
+        .find(|(_, m)| len(m) != len(first))
+    {
+        let (expected, got) = (len(first), len(m));
+        return Err(match d {
+            1 => StackErr::HeightMismatch { index, expected, got },
+            _ => StackErr::WidthMismatch { index, expected, got },
+        });
+    }
+
+    let tensors: Vec<_> = matrices.iter().map(|m| &m.0).collect();
+    match concat(&tensors, d) {
+        Ok(t) => Ok(Matrix(t)),
+        Err(ConcatErr::Len(e)) => Err(StackErr::Len(e)),
+        // The only other dims to check are unused in any matrix.
+        Err(e) => unreachable!("matrices differ in a dim besides height and width: {:?}", e),
+    }
+}
+
+/// Panic for an index into an HxW matrix which has no element there,
+/// saying whether that's because it's out of range, or there's no data.
+#[cold]
+fn index_panic(row: usize, col: usize, h: usize, w: usize) -> ! {
+    if row < h && col < w {
+        panic!("cannot index into a matrix with no data")
+    }
+    panic!("index ({}, {}) out of range for a {}x{} matrix", row, col, h, w)
+}
+
+/// Check that every inner vec of `md_arr` is as long as the first, and
+/// that both that length and the number of vecs can be dims, returning
+/// them as `(inner, outer)`, i.e. `(h, w)` for cols, `(w, h)` for rows.
+fn check_ragged<T>(md_arr: &[Vec<T>]) -> Result<(usize, usize), RaggedErr> {
+    let expected = md_arr.first().map_or(0, |m| m.len());
+    if let Some((index, m)) = md_arr
+        .iter()
+        .enumerate()
+        .find(|(_, m)| m.len() != expected)
+    {
+        return Err(RaggedErr::LenMismatch { index, expected, got: m.len() });
+    }
+
+    try_dim(expected)?;
+    try_dim(md_arr.len())?;
+    Ok((expected, md_arr.len()))
+}
+
+/// Panic for a 2D vec which [`check_ragged`] rejected, naming the kind
+/// of vec, i.e. `"row"` or `"column"`, each inner vec is read as.
+#[cold]
+fn ragged_panic(e: RaggedErr, kind: &str) -> ! {
+    match e {
+        RaggedErr::LenMismatch { index, expected, got } => {
+            panic!("{} {} has {} elements, but the first has {}", kind, index, got, expected)
+        }
+        RaggedErr::Len(LenErr::Empty) => panic!("cannot build a matrix with no elements"),
+        RaggedErr::Len(e) => panic!("cannot build a matrix: {:?}", e),
+    }
 }
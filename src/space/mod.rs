@@ -1,53 +1,946 @@
-use alloc::boxed::Box;
+use alloc::string::String;
 use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::fmt;
+pub use core::num::NonZeroU16 as NzU16;
+use core::ops::Range;
 
+use crate::invar::{CastAs, Float};
+
+mod bytes;
 mod matrix;
+#[cfg(feature = "rand")] mod random;
+#[cfg(feature = "serde")] mod serial;
+mod smatrix;
 mod vector;
+mod view;
 
+pub use bytes::DeserErr;
 pub use matrix::Matrix;
+pub use smatrix::SMatrix;
 pub use vector::Vector;
+pub use view::TensorView;
 
 /// An ordered set on which mathematical ops are defined.
 /// Column major for storage, and e.g. when iterating.
+#[derive(Clone, PartialEq)]
 pub struct Tensor<T> {
-    data: Option<Vec<T>>,
-    /// Dimensionality of the tensor.
+    pub(crate) data: Option<Vec<T>>,
+    /// Dimensionality of the tensor. Unused dims have length 1.
     // 8x u16s for dimension lens - this fits in 2 words,
     // enforces nonzeroity, and easy to expand.
-    dims: [u16; 8],
+    pub(crate) dims: [NzU16; 8],
+}
+
+/// The length of a dimension which is not in use, e.g. the 3rd to
+/// 8th dims of a matrix. Since this is the multiplicative identity,
+/// unused dims don't affect the product of the dims.
+pub(crate) const UNUSED: NzU16 = match NzU16::new(1) {
+    Some(d) => d,
+    None => unreachable!(),
+};
+
+/// The most elements which [`Tensor`]'s [`fmt::Debug`] impl prints.
+/// Any more than this are elided, so that big tensors stay readable.
+const DEBUG_LEN: usize = 16;
+
+/// The longest row or column which the [`fmt::Display`] impls of
+/// [`Matrix`] and [`Vector`] print in full. Any longer is cut down to
+/// the first & last [`DISPLAY_EDGE`] elements, with `...` in between.
+const DISPLAY_MAX: usize = 10;
+
+/// How many elements at each end of a row or column too long to print
+/// in full are printed, as NumPy does.
+const DISPLAY_EDGE: usize = 3;
+
+/// The indices of a row or column of length `len` to print, where `None`
+/// stands for the elided `...` between the two ends, if `len` is longer
+/// than [`DISPLAY_MAX`].
+fn display_indices(len: usize) -> Vec<Option<usize>> {
+    if len <= DISPLAY_MAX {
+        (0..len).map(Some).collect()
+    } else {
+        (0..DISPLAY_EDGE)
+            .map(Some)
+            .chain([None])
+            .chain((len - DISPLAY_EDGE..len).map(Some))
+            .collect()
+    }
+}
+
+/// Format `x` as [`fmt::Display`] does, honouring the precision of `f`,
+/// if any, so that e.g. `{:.2}` applies to each element of a matrix.
+fn display_elem<T: fmt::Display>(x: &T, f: &fmt::Formatter<'_>) -> String {
+    match f.precision() {
+        Some(p) => format!("{:.*}", p, x),
+        None => format!("{}", x),
+    }
+}
+
+/// Convert a length to a dimension, if it is in range, i.e. nonzero
+/// and small enough to fit in a [`u16`].
+pub(crate) fn try_dim(len: usize) -> Result<NzU16, LenErr> {
+    match u16::try_from(len) {
+        Ok(l) => NzU16::new(l).ok_or(LenErr::Empty),
+        Err(_) => Err(LenErr::Overflow(len)),
+    }
+}
+
+/// Convert a length to a dimension. This panics if `len` is zero or
+/// if it doesn't fit in a [`u16`], rather than silently truncating.
+pub(crate) fn dim(len: usize) -> NzU16 {
+    try_dim(len).unwrap_or_else(|_| panic!("dimension length {} not in range 1..=65535", len))
+}
+
+/// Convert up to 8 lengths to a full set of dims, padding with the
+/// [`UNUSED`] length. Each length is converted as [`dim`] does.
+pub(crate) fn shape(lens: &[usize]) -> [NzU16; 8] {
+    let mut dims = [UNUSED; 8];
+    dims.iter_mut()
+        .zip(lens)
+        .for_each(|(d, &len)| *d = dim(len));
+    dims
+}
+
+/// The number of elements in a tensor of the given dimensions. This
+/// panics if there are more than a `usize` can hold, which can't be
+/// the case for any tensor with data: see [`checked_size`] otherwise.
+pub(crate) fn size(dims: &[NzU16; 8]) -> usize {
+    checked_size(dims).unwrap_or_else(|| panic!("tensor of dims {:?} is too large to hold", dims))
+}
+
+/// The number of elements in a tensor of the given dimensions, or
+/// `None` if there are more than a `usize` can hold, e.g. to validate
+/// dims which a user gave, before trusting them.
+pub(crate) fn checked_size(dims: &[NzU16; 8]) -> Option<usize> {
+    dims.iter()
+        .try_fold(1usize, |acc, &d| acc.checked_mul(d.get() as usize))
+}
+
+/// The column-major strides of a tensor of the given dimensions, i.e.
+/// how far apart in memory consecutive elements of each dim are. The
+/// first dim is contiguous, and each one after it strides over all
+/// of the dims before it.
+pub(crate) fn strides(dims: &[NzU16; 8]) -> [usize; 8] {
+    let mut strides = [1; 8];
+    (1..8).for_each(|d| strides[d] = strides[d - 1] * dims[d - 1].get() as usize);
+    strides
+}
+
+/// The flat, column-major offset of the element at `idx` in a tensor
+/// of the given dims. Omitted trailing coordinates are taken as `0`.
+/// Returns `None` if any coordinate is out of range for its dim.
+pub(crate) fn offset(dims: &[NzU16; 8], idx: &[usize]) -> Option<usize> {
+    if idx.len() > dims.len() {
+        return None;
+    }
+
+    idx.iter()
+        .zip(dims.iter().zip(strides(dims)))
+        .try_fold(0, |acc, (&i, (&d, stride))| (i < d.get() as usize).then(|| acc + i * stride))
+}
+
+/// An error returned when a length can't be used as a dimension.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum LenErr {
+    /// The length is zero. Every dimension has a length of at least 1.
+    Empty,
+    /// The length is greater than [`u16::MAX`], which is the longest
+    /// any dimension can be. This holds the length that was given.
+    Overflow(usize),
+}
+
+/// An error returned by [`Tensor::try_from_vec`], [`Matrix::from_flat`],
+/// and [`Matrix::from_flat_rows`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ShapeErr {
+    /// The data doesn't hold as many elements as the dims call for, e.g.
+    /// `rows * cols`. `expected` is the number it should have held, and
+    /// `got` the number it did hold.
+    SizeMismatch { expected: usize, got: usize },
+    /// A length, e.g. the number of rows, can't be used as a dimension.
+    Len(LenErr),
+    /// The product of the dims is more than a `usize` can hold, so no
+    /// data could hold that many elements.
+    Overflow,
+}
+
+impl From<LenErr> for ShapeErr {
+    fn from(e: LenErr) -> Self {
+        ShapeErr::Len(e)
+    }
+}
+
+/// An error returned by [`Matrix::try_from_rows`] and
+/// [`Matrix::try_from_cols`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum RaggedErr {
+    /// The inner vec at `index`, i.e. a row or column, holds `got`
+    /// elements, but the first holds `expected`, so they can't all be
+    /// rows (or columns) of one matrix.
+    LenMismatch { index: usize, expected: usize, got: usize },
+    /// The number of rows or columns can't be used as a dimension.
+    Len(LenErr),
+}
+
+impl From<LenErr> for RaggedErr {
+    fn from(e: LenErr) -> Self {
+        RaggedErr::Len(e)
+    }
+}
+
+/// An error returned by [`Tensor::reshape`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ReshapeErr {
+    /// The new dims don't hold the same number of elements as the
+    /// old dims. `expected` is the old count, and `got` the new one.
+    SizeMismatch { expected: usize, got: usize },
+    /// The product of the new dims is more than a `usize` can hold, so
+    /// can't be the same as the old count.
+    Overflow,
+}
+
+/// An error returned by [`Tensor::broadcast_to`] or [`Tensor::try_add`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum BroadcastErr {
+    /// Dim `dim` has length `from`, which is neither `1` nor the same
+    /// as the length `to` it was to be broadcast to.
+    DimMismatch { dim: usize, from: u16, to: u16 },
+    /// The RHS has `rhs` elements, which doesn't divide the `lhs` that
+    /// the LHS has, so repeating it would misalign the two partway.
+    Indivisible { lhs: usize, rhs: usize },
+}
+
+/// An error returned by [`Tensor::concat`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ConcatErr {
+    /// There were no tensors to concatenate.
+    Empty,
+    /// The tensor at `index` differs from the first in dim `dim`, which
+    /// isn't the one they're concatenated along, so they can't stack.
+    DimMismatch { index: usize, dim: usize },
+    /// The concatenated dim would be too long to be a dimension.
+    Len(LenErr),
+}
+
+/// An error returned by [`Tensor::stack`], [`Matrix::hstack`], and
+/// [`Matrix::vstack`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum StackErr {
+    /// There was nothing to stack, i.e. no matrices or tensors at all.
+    Empty,
+    /// The matrix at `index` is `got` high, but the first is `expected`
+    /// high, so they can't be stacked side by side.
+    HeightMismatch { index: usize, expected: usize, got: usize },
+    /// The matrix at `index` is `got` wide, but the first is `expected`
+    /// wide, so they can't be stacked one atop another.
+    WidthMismatch { index: usize, expected: usize, got: usize },
+    /// The stacked matrix would be too wide or too high to be a matrix,
+    /// or there are too many tensors for the new dim to hold them.
+    Len(LenErr),
+    /// The tensor at `index` differs from the first in dim `dim`, but
+    /// they must all have the same dims to be stacked.
+    DimMismatch { index: usize, dim: usize },
+    /// The new dim would be at `dim`, but that's after the tensors' last
+    /// dim, i.e. their `rank`, or they already use all 8 dims.
+    OutOfRange { dim: usize, rank: usize },
+}
+
+impl fmt::Display for StackErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StackErr::Empty => f.write_str("no tensors to stack"),
+            StackErr::HeightMismatch { index, expected, got } => write!(
+                f,
+                "all matrices must have height {}, but matrix {} has height {}",
+                expected, index, got
+            ),
+            StackErr::WidthMismatch { index, expected, got } => write!(
+                f,
+                "all matrices must have width {}, but matrix {} has width {}",
+                expected, index, got
+            ),
+            StackErr::Len(e) => write!(f, "stacked tensor is too large: {:?}", e),
+            StackErr::DimMismatch { index, dim } => {
+                write!(
+                    f,
+                    "all tensors must have the same dims, but tensor {} differs in dim {}",
+                    index, dim
+                )
+            }
+            StackErr::OutOfRange { dim, rank } => {
+                write!(f, "cannot stack tensors of rank {} along a new dim {}", rank, dim)
+            }
+        }
+    }
+}
+
+impl core::error::Error for StackErr {}
+
+/// An error returned by [`Tensor::split`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum SplitErr {
+    /// The sizes of the pieces sum to `got`, not to `expected`, which
+    /// is the length of the dim being split.
+    SizeMismatch { expected: usize, got: usize },
+    /// The size at `index` is zero, but every dim has length 1+.
+    Empty { index: usize },
+    /// The sizes up to and including the one at `index` sum to more
+    /// than a `usize` can hold, so can't be the length of any dim.
+    Overflow { index: usize },
 }
 
-/// A raw multidimensional array of a tensor's contents.
-pub type TensorData<T> = Box<[T]>;
+/// An error returned by [`Tensor::slice`] and [`TensorView::slice`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum SliceErr {
+    /// More than 8 ranges were given, but tensors only have 8 dims.
+    TooManyRanges(usize),
+    /// The range for dim `dim` is empty, but every dim has length 1+.
+    Empty { dim: usize },
+    /// The range for dim `dim` ends after `len`, the length of `dim`.
+    OutOfRange { dim: usize, range: Range<usize>, len: usize },
+}
+
+/// An error returned by [`Tensor::permute`] and [`TensorView::permute`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum PermuteErr {
+    /// The order isn't a permutation of `0..n`, where `n` is its length,
+    /// i.e. some dim is missing, repeated, or out of range.
+    NotPermutation,
+    /// The order is longer than 8, but tensors only have 8 dims.
+    TooManyDims(usize),
+}
+
+/// ## Constructors
+impl<T> Tensor<T> {
+    /// Create a tensor of the given dims from a flat [`Vec`], in the
+    /// column-major order it's stored in. The data must hold exactly as
+    /// many elements as the product of the dims, or this returns an
+    /// error, as it does if that product overflows a `usize`. (The dims
+    /// themselves can't be out of range, as [`NzU16`] rules out zero,
+    /// and anything longer than [`u16::MAX`].)
+    pub fn try_from_vec(data: Vec<T>, dims: [NzU16; 8]) -> Result<Tensor<T>, ShapeErr> {
+        let expected = checked_size(&dims).ok_or(ShapeErr::Overflow)?;
+        if data.len() != expected {
+            return Err(ShapeErr::SizeMismatch { expected, got: data.len() });
+        }
+
+        Ok(Tensor { data: Some(data), dims })
+    }
+
+    /// Create a new [`Tensor`] of the given dims, in which every one
+    /// of the elements is zero, i.e. [`Default`]. See [`Tensor::filled`].
+    pub fn zeros(dims: [NzU16; 8]) -> Tensor<T>
+    where
+        T: Default + Clone,
+    {
+        Self::filled(dims, T::default())
+    }
+
+    /// Create a new [`Tensor`] of the given dims, in which every one
+    /// of the elements is `value`. The data is allocated in one go, at
+    /// the size of the product of `dims`, and laid out column-major, as
+    /// always, so it can be written to in place in that order. This
+    /// panics if the product of `dims` is more than a `usize` can hold.
+    pub fn filled(dims: [NzU16; 8], value: T) -> Tensor<T>
+    where
+        T: Clone,
+    {
+        Tensor { data: Some(vec![value; size(&dims)]), dims }
+    }
+}
 
 /// ## Accessors
 impl<T> Tensor<T> {
-    pub fn data(&self) -> Option<Vec<T>> {
+    /// Borrow the contents of this tensor, in column-major order.
+    pub fn data(&self) -> Option<&[T]> {
+        self.data.as_deref()
+    }
+
+    /// Borrow the contents of this tensor, in column-major order, as
+    /// [`Tensor::data`] does, for code which knows it has data, e.g. in
+    /// a hot loop, without unwrapping at every call. A tensor is always
+    /// contiguous, so this never copies. This panics if there's no data.
+    pub fn contiguous_data(&self) -> &[T] {
         self.data
+            .as_deref()
+            .expect("cannot borrow the data of a tensor with no data")
+    }
+
+    /// Borrow the contents of this tensor, as [`Tensor::contiguous_data`]
+    /// does, but returning `None` if there's no data, rather than
+    /// panicking. This is the same as [`Tensor::data`], by another name.
+    pub fn try_contiguous_data(&self) -> Option<&[T]> {
+        self.data()
+    }
+
+    /// Take ownership of the contents of this tensor, consuming it.
+    pub fn into_data(self) -> Option<Vec<T>> {
+        self.data
+    }
+
+    /// Borrow the element at the coordinates `idx`, one per dim, which
+    /// are translated to a column-major offset. Coordinates omitted at
+    /// the end are `0`. Returns `None` if any are out of range for the
+    /// dim they index, or there are more coordinates than dims.
+    pub fn get(&self, idx: &[usize]) -> Option<&T> {
+        self.data.as_ref()?.get(offset(&self.dims, idx)?)
+    }
+
+    /// Mutably borrow the element at the coordinates `idx`. This works
+    /// in exactly the same way as [`Tensor::get`].
+    pub fn get_mut(&mut self, idx: &[usize]) -> Option<&mut T> {
+        self.data
+            .as_mut()?
+            .get_mut(offset(&self.dims, idx)?)
     }
 }
 
 /// ## Shape methods
 impl<T> Tensor<T> {
     /// Get the dimensions of this tensor.
-    pub fn dims(&self) -> [u16; 8] {
+    pub fn dims(&self) -> [NzU16; 8] {
         self.dims
     }
 
     /// Get the horizontal length of this tensor.
     pub fn hlen(&self) -> usize {
-        u16::from(self.dims[1]) as usize
+        self.dims[1].get() as usize
     }
 
     /// Get the vertical length of this tensor.
     pub fn vlen(&self) -> usize {
-        u16::from(self.dims[0]) as usize
+        self.dims[0].get() as usize
+    }
+
+    /// Get the rank of this tensor, i.e. the number of dims up to and
+    /// including the last one that is longer than 1. Dims of length 1
+    /// before that one still count, so a 1x3 matrix has rank 2, but a
+    /// 3x1 matrix has rank 1, like a vector, and a 1x1 has rank 0.
+    pub fn rank(&self) -> usize {
+        self.dims
+            .iter()
+            .rposition(|&d| d != UNUSED)
+            .map_or(0, |d| d + 1)
+    }
+
+    /// Get the number of elements of this tensor, i.e. the product of
+    /// its dims. A tensor of rank 0 has exactly one element.
+    pub fn numel(&self) -> usize {
+        size(&self.dims)
     }
 
     /// Get the length for a numbered, **zero-indexed** dimension.
     pub fn len_for(&self, d: usize) -> u16 {
-        self.dims[d].into()
+        self.dims[d].get()
+    }
+
+    /// Reinterpret this tensor as having the dimensions `dims`. This
+    /// doesn't touch the data, which stays in the same (column-major)
+    /// order, so it costs nothing. Unused dims must be given as `1`:
+    /// [`NzU16`] rules out `0`, so the product of `dims` is then the
+    /// number of elements, which must be the same as it was before.
+    pub fn reshape(self, dims: [NzU16; 8]) -> Result<Tensor<T>, ReshapeErr> {
+        let expected = size(&self.dims);
+        let got = checked_size(&dims).ok_or(ReshapeErr::Overflow)?;
+        if expected != got {
+            return Err(ReshapeErr::SizeMismatch { expected, got });
+        }
+
+        Ok(Tensor { data: self.data, dims })
+    }
+
+    /// Whether this tensor's data is laid out contiguously, in column-major
+    /// order. A tensor owns its data, so this is always true: it's only
+    /// [`TensorView`]s, which may be strided, for which it can be false.
+    pub fn is_contiguous(&self) -> bool {
+        true
+    }
+
+    /// Copy this tensor into one whose data is laid out contiguously,
+    /// in column-major order. Since every tensor already is, this is
+    /// just a clone. To make a contiguous copy of a strided view, call
+    /// [`TensorView::to_owned`].
+    pub fn to_contiguous(&self) -> Tensor<T>
+    where
+        T: Clone,
+    {
+        self.clone()
+    }
+
+    /// Reinterpret this tensor as a [`Vector`] of all of its elements,
+    /// in the same (column-major) order, so this costs nothing. This
+    /// fails if it has more elements than a single dim can hold.
+    pub fn flatten(self) -> Result<Vector<T>, LenErr> {
+        let mut dims = [UNUSED; 8];
+        dims[0] = try_dim(size(&self.dims))?;
+
+        Ok(Vector(Tensor { data: self.data, dims }))
+    }
+
+    /// Remove every dim of length 1, moving the dims after each one
+    /// down to close the gap, so e.g. a 1x4x1 tensor becomes a vector
+    /// of length 4. As with [`Tensor::reshape`], the data is untouched.
+    pub fn squeeze(self) -> Tensor<T> {
+        let mut dims = [UNUSED; 8];
+        dims.iter_mut()
+            .zip(self.dims.iter().filter(|&&d| d != UNUSED))
+            .for_each(|(to, &from)| *to = from);
+
+        Tensor { data: self.data, dims }
+    }
+
+    /// Remove dim `d`, which must be of length 1, moving the dims after
+    /// it down to close the gap. This panics if `d` is longer than 1,
+    /// as removing it would change the number of elements.
+    pub fn squeeze_axis(self, d: usize) -> Tensor<T> {
+        assert!(d < 8, "tensors have 8 dims, so dim {} is out of range", d);
+        assert!(self.dims[d] == UNUSED, "cannot squeeze dim {} of length {}", d, self.dims[d]);
+
+        let mut dims = self.dims;
+        dims[d..].rotate_left(1);
+        Tensor { data: self.data, dims }
+    }
+}
+
+/// ## Broadcasting
+impl<T: Clone> Tensor<T> {
+    /// Broadcast this tensor to the dimensions `dims`, by repeating it
+    /// along each dim it has length 1 in. The rules are NumPy's, save
+    /// that dims are matched by index, not aligned from the last one:
+    /// as every tensor has 8 dims, with unused ones 1, that's the same.
+    ///
+    /// - A dim of the same length as in `dims` is left as it is.
+    /// - A dim of length 1 is repeated to the length in `dims`.
+    /// - Any other dim is an error, as there's no sane way to fill it.
+    pub fn broadcast_to(&self, dims: [NzU16; 8]) -> Result<Tensor<T>, BroadcastErr> {
+        if let Some(d) = (0..8).find(|&d| self.dims[d] != dims[d] && self.dims[d] != UNUSED) {
+            return Err(BroadcastErr::DimMismatch { dim: d, from: self.dims[d].get(), to: dims[d].get() });
+        }
+
+        // A broadcast dim always has coordinate 0 in this tensor, so we
+        // skip it by giving it a stride of 0, and every other dim keeps
+        // its stride. Each element then comes from the sum over dims.
+        let mut from = strides(&self.dims);
+        (0..8).for_each(|d| from[d] *= (self.dims[d] == dims[d]) as usize);
+        let to = strides(&dims);
+
+        let data = self.data.as_ref().map(|data| {
+            (0..size(&dims))
+                .map(|i| {
+                    let j = (0..8)
+                        .map(|d| i / to[d] % dims[d].get() as usize * from[d])
+                        .sum::<usize>();
+                    data[j].clone()
+                })
+                .collect()
+        });
+
+        Ok(Tensor { data, dims })
+    }
+
+    /// Repeat this tensor `reps[d]` times along each dim `d`, as NumPy's
+    /// `tile` does, e.g. to fill a matrix with copies of a bias vector.
+    /// Dims without a count in `reps` are repeated once, i.e. left as
+    /// they are. This panics if there are more than 8 counts, or any of
+    /// them is 0, or the repeated dim would be longer than [`u16::MAX`].
+    pub fn tile(&self, reps: &[usize]) -> Tensor<T> {
+        assert!(reps.len() <= 8, "tensors have 8 dims, so {} counts is too many", reps.len());
+
+        let mut dims = self.dims;
+        reps.iter()
+            .enumerate()
+            .for_each(|(d, &r)| dims[d] = dim(self.dims[d].get() as usize * r));
+
+        // The copies are laid end to end, so each coordinate of the result
+        // maps to itself modulo the length of its dim in this tensor, and
+        // each element is then read from there, as in `broadcast_to`.
+        let (from, to) = (strides(&self.dims), strides(&dims));
+        let data = self.data.as_ref().map(|data| {
+            (0..size(&dims))
+                .map(|i| {
+                    let j = (0..8)
+                        .map(|d| i / to[d] % dims[d].get() as usize % self.dims[d].get() as usize * from[d])
+                        .sum::<usize>();
+                    data[j].clone()
+                })
+                .collect()
+        });
+
+        Tensor { data, dims }
+    }
+}
+
+/// ## Concatenation
+impl<T: Clone> Tensor<T> {
+    /// Concatenate `tensors` along dim `d`, in order. Every tensor must
+    /// have the same dims as the others, except for `d`, whose length
+    /// in the result is the sum of theirs. If any of them has no data,
+    /// neither does the result.
+    pub fn concat(tensors: &[Tensor<T>], d: usize) -> Result<Tensor<T>, ConcatErr> {
+        concat(&tensors.iter().collect::<Vec<_>>(), d)
+    }
+
+    /// Stack `tensors` along a new dim `d`, in order, i.e. the result has
+    /// one more dim than they do, e.g. stacking N HxW matrices along dim
+    /// 0 batches them into an NxHxW tensor. Unlike [`Tensor::concat`],
+    /// which joins them along a dim they already have, they must all
+    /// have the same dims, and `d` may be at most their rank. The dims
+    /// from `d` on are moved up one. If any of them has no data, neither
+    /// does the result.
+    pub fn stack(tensors: &[Tensor<T>], d: usize) -> Result<Tensor<T>, StackErr> {
+        let first = tensors.first().ok_or(StackErr::Empty)?;
+        let rank = first.rank();
+        if d > rank || first.dims[7] != UNUSED {
+            return Err(StackErr::OutOfRange { dim: d, rank });
+        }
+        for (index, t) in tensors.iter().enumerate() {
+            if let Some(dim) = (0..8).find(|&e| t.dims[e] != first.dims[e]) {
+                return Err(StackErr::DimMismatch { index, dim });
+            }
+        }
+
+        let mut dims = first.dims;
+        dims.copy_within(d..7, d + 1);
+        dims[d] = try_dim(tensors.len()).map_err(StackErr::Len)?;
+
+        // As in `concat`, with each tensor of length 1 in the new dim: in
+        // column-major order, the result interleaves the tensors' blocks
+        // of `inner` elements, i.e. of one step along the dims before `d`.
+        let inner = strides(&dims)[d];
+        let data = tensors
+            .iter()
+            .map(|t| t.data.as_deref())
+            .collect::<Option<Vec<_>>>()
+            .map(|parts| {
+                let mut data = Vec::with_capacity(size(&dims));
+                (0..size(&first.dims) / inner).for_each(|o| {
+                    parts
+                        .iter()
+                        .for_each(|part| data.extend_from_slice(&part[o * inner..(o + 1) * inner]));
+                });
+                data
+            });
+
+        Ok(Tensor { data, dims })
+    }
+
+    /// Split this tensor along dim `d` into pieces of the given `sizes`,
+    /// in order, i.e. the inverse of [`Tensor::concat`], e.g. to unbatch
+    /// a batch of outputs. The sizes must sum to the length of `d`, and
+    /// each be at least 1. If this tensor has no data, nor do the pieces.
+    pub fn split(&self, d: usize, sizes: &[usize]) -> Result<Vec<Tensor<T>>, SplitErr> {
+        assert!(d < 8, "tensors have 8 dims, so dim {} is out of range", d);
+
+        let len = self.dims[d].get() as usize;
+        let got = sizes
+            .iter()
+            .enumerate()
+            .try_fold(0usize, |acc, (index, &s)| {
+                acc.checked_add(s)
+                    .ok_or(SplitErr::Overflow { index })
+            })?;
+        if got != len {
+            return Err(SplitErr::SizeMismatch { expected: len, got });
+        }
+        if let Some(index) = sizes.iter().position(|&s| s == 0) {
+            return Err(SplitErr::Empty { index });
+        }
+
+        // As in `concat`, this tensor is a run of `outer` blocks, each
+        // of which holds a block of every piece in turn, so piece `k`
+        // takes the same stretch of each, starting `start` elements in.
+        let stride = strides(&self.dims)[d];
+        let outer = size(&self.dims) / (stride * len);
+        let mut start = 0;
+        Ok(sizes
+            .iter()
+            .map(|&s| {
+                let mut dims = self.dims;
+                // Safe to unwrap: It's between 1 and the length of `d`.
+                dims[d] = NzU16::new(s as u16).unwrap();
+
+                let (from, block) = (start * stride, s * stride);
+                start += s;
+                let data = self.data.as_deref().map(|data| {
+                    let mut piece = Vec::with_capacity(size(&dims));
+                    (0..outer).for_each(|o| {
+                        let at = o * stride * len + from;
+                        piece.extend_from_slice(&data[at..at + block]);
+                    });
+                    piece
+                });
+
+                Tensor { data, dims }
+            })
+            .collect())
+    }
+}
+
+/// Concatenate `tensors` along dim `d`, as [`Tensor::concat`] does, but
+/// from borrows, so that e.g. matrices can be stacked without clones.
+pub(crate) fn concat<T: Clone>(tensors: &[&Tensor<T>], d: usize) -> Result<Tensor<T>, ConcatErr> {
+    assert!(d < 8, "tensors have 8 dims, so dim {} is out of range", d);
+
+    let first = tensors.first().ok_or(ConcatErr::Empty)?;
+    for (index, t) in tensors.iter().enumerate() {
+        if let Some(dim) = (0..8).find(|&e| e != d && t.dims[e] != first.dims[e]) {
+            return Err(ConcatErr::DimMismatch { index, dim });
+        }
+    }
+
+    let mut dims = first.dims;
+    dims[d] = try_dim(
+        tensors
+            .iter()
+            .map(|t| t.dims[d].get() as usize)
+            .sum(),
+    )
+    .map_err(ConcatErr::Len)?;
+
+    // In column-major order, each tensor is a run of `outer` blocks,
+    // each holding `stride * len` elements, where `len` is its own
+    // length in `d`. So the result interleaves the tensors' blocks:
+    // all of their first blocks in turn, then all their second, etc.
+    let stride = strides(&dims)[d];
+    let outer = size(&dims) / (stride * dims[d].get() as usize);
+    let data = tensors
+        .iter()
+        .map(|t| t.data.as_deref())
+        .collect::<Option<Vec<_>>>()
+        .map(|parts| {
+            let mut data = Vec::with_capacity(size(&dims));
+            (0..outer).for_each(|o| {
+                parts.iter().zip(tensors).for_each(|(part, t)| {
+                    let block = stride * t.dims[d].get() as usize;
+                    data.extend_from_slice(&part[o * block..(o + 1) * block]);
+                });
+            });
+            data
+        });
+
+    Ok(Tensor { data, dims })
+}
+
+/// ## Slicing
+impl<T: Clone> Tensor<T> {
+    /// Copy out the sub-tensor covering `ranges`, one range per dim,
+    /// into a new tensor whose dims are the lengths of the ranges. Any
+    /// dims past the last range are taken in full. So the bottom-right
+    /// 2x2 of a 4x4 matrix is `slice(&[2..4, 2..4])`.
+    pub fn slice(&self, ranges: &[Range<usize>]) -> Result<Tensor<T>, SliceErr> {
+        Ok(self.view().slice(ranges)?.to_owned())
+    }
+}
+
+/// ## Padding
+impl<T: Clone> Tensor<T> {
+    /// Pad this tensor with `fill`, growing each dim `d` by `before[d]`
+    /// elements before the data, and `after[d]` elements after it. Any
+    /// dims past the end of `before` or `after` aren't padded on that
+    /// side. This panics if any dim would grow past [`u16::MAX`].
+    pub fn pad(&self, before: &[usize], after: &[usize], fill: T) -> Tensor<T> {
+        assert!(
+            before.len() <= 8 && after.len() <= 8,
+            "tensors have 8 dims, so can't be padded in more"
+        );
+
+        let (mut starts, mut dims) = ([0; 8], self.dims);
+        (0..8).for_each(|d| {
+            starts[d] = before.get(d).copied().unwrap_or(0);
+            let len = self.dims[d].get() as usize + starts[d] + after.get(d).copied().unwrap_or(0);
+            dims[d] = dim(len);
+        });
+
+        // Each element of this tensor moves to its own coordinates, offset
+        // by the padding before it in each dim, in the padded strides.
+        let (from, to) = (strides(&self.dims), strides(&dims));
+        let data = self.data.as_ref().map(|data| {
+            let mut padded = vec![fill; size(&dims)];
+            data.iter().enumerate().for_each(|(i, x)| {
+                let j = (0..8)
+                    .map(|d| (starts[d] + i / from[d] % self.dims[d].get() as usize) * to[d])
+                    .sum::<usize>();
+                padded[j] = x.clone();
+            });
+            padded
+        });
+
+        Tensor { data, dims }
     }
 }
 
-impl<T> Tensor<T> {}
+/// ## Permutation
+impl<T: Clone> Tensor<T> {
+    /// Reorder the dims of this tensor, so that dim `k` of the result is
+    /// dim `order[k]` of this one, moving the data to match. This is the
+    /// N-dimensional [`Matrix::transpose`]: for a matrix, that's `[1, 0]`.
+    /// `order` must be a permutation of `0..n` for some `n`, and any dims
+    /// from `n` on stay where they are, so `n` may be as small as the
+    /// [`Tensor::rank`]. A permutation is undone by its inverse.
+    pub fn permute(&self, order: &[usize]) -> Result<Tensor<T>, PermuteErr> {
+        Ok(self.view().permute(order)?.to_owned())
+    }
+}
+
+/// ## Comparison
+impl<T: Float> Tensor<T> {
+    /// Whether this tensor and `other` have the same dims, and every
+    /// pair of elements is within `eps` of each other, i.e. `|a - b| <=
+    /// eps`. Exact equality is rarely useful for floats, least of all
+    /// for results computed at half precision. NaN is never equal.
+    pub fn approx_eq(&self, other: &Self, eps: T) -> bool {
+        self.dims == other.dims
+            && match (&self.data, &other.data) {
+                (Some(l), Some(r)) => l
+                    .iter()
+                    .zip(r)
+                    .all(|(&a, &b)| a - b <= eps && b - a <= eps),
+                (None, None) => true,
+                _ => false,
+            }
+    }
+}
+
+/// ## Combinators
+impl<T> Tensor<T> {
+    /// Apply `f` to each element of this tensor, returning a tensor of
+    /// the same dims holding the results, which may be of a different
+    /// type. A tensor without data maps to a tensor without data.
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> Tensor<U> {
+        Tensor {
+            data: self
+                .data
+                .as_ref()
+                .map(|d| d.iter().map(f).collect()),
+            dims: self.dims,
+        }
+    }
+
+    /// Convert each element of this tensor to `U`, losslessly, e.g. from
+    /// `u8` to `u32`, or `i32` to `f64`, keeping the dims as they are.
+    /// For the conversions which can lose information, e.g. from `f32`
+    /// to `i32`, see [`Tensor::cast_lossy`].
+    pub fn cast<U>(&self) -> Tensor<U>
+    where
+        T: Into<U> + Clone,
+    {
+        self.map(|t| t.clone().into())
+    }
+
+    /// Convert each element of this tensor to `U`, as the `as` operator
+    /// does, keeping the dims as they are. This may lose information:
+    /// see [`CastAs`] for how. If it mustn't, use [`Tensor::cast`].
+    pub fn cast_lossy<U>(&self) -> Tensor<U>
+    where
+        T: CastAs<U>,
+    {
+        self.map(|&t| t.cast_as())
+    }
+
+    /// Apply `f` to each element of this tensor in place, e.g. an
+    /// activation function, so that, unlike [`Tensor::map`], nothing is
+    /// allocated. A tensor without data is left as it is.
+    pub fn apply_inplace<F: FnMut(&mut T)>(&mut self, f: F) {
+        if let Some(d) = &mut self.data {
+            d.iter_mut().for_each(f);
+        }
+    }
+
+    /// Apply `f` to each pair of elements of this tensor and `other`,
+    /// returning a tensor of the same dims holding the results. Both
+    /// tensors must have the same dims, or this will panic. If either
+    /// one has no data, neither does the result.
+    pub fn zip_map<U, V, F: FnMut(&T, &U) -> V>(&self, other: &Tensor<U>, mut f: F) -> Tensor<V> {
+        assert!(self.dims == other.dims, "cannot zip tensors of different dims");
+
+        Tensor {
+            data: match (&self.data, &other.data) {
+                (Some(l), Some(r)) => Some(l.iter().zip(r).map(|(a, b)| f(a, b)).collect()),
+                _ => None,
+            },
+            dims: self.dims,
+        }
+    }
+
+    /// Compare each element of this tensor to `k`, returning a tensor of
+    /// the same dims holding whether it's greater, i.e. a mask, e.g. for
+    /// [`Tensor::select`]. NaNs are greater than nothing, so map to false.
+    pub fn gt(&self, k: T) -> Tensor<bool>
+    where
+        T: PartialOrd,
+    {
+        self.map(|t| *t > k)
+    }
+}
+
+impl<T: Clone> Tensor<T> {
+    /// Pick each element from `a` where `mask` is true, and from `b`
+    /// where it's false, returning a tensor of the same dims holding
+    /// them, i.e. an elementwise ternary, as NumPy's `where` is, e.g.
+    /// for masking. All three must have the same dims, or this will
+    /// panic. If any of them has no data, neither does the result.
+    pub fn select(mask: &Tensor<bool>, a: &Tensor<T>, b: &Tensor<T>) -> Tensor<T> {
+        assert!(
+            mask.dims == a.dims && a.dims == b.dims,
+            "cannot select between tensors of different dims"
+        );
+
+        Tensor {
+            data: match (&mask.data, &a.data, &b.data) {
+                (Some(m), Some(a), Some(b)) => Some(
+                    m.iter()
+                        .zip(a.iter().zip(b))
+                        .map(|(&m, (a, b))| if m { a.clone() } else { b.clone() })
+                        .collect(),
+                ),
+                _ => None,
+            },
+            dims: mask.dims,
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Tensor<T> {
+    /// Prints the dims and the data, in column-major order. Trailing
+    /// unused dims are left out, and only the first [`DEBUG_LEN`]
+    /// elements of the data are printed, followed by `..` if elided.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let used = self
+            .dims
+            .iter()
+            .rposition(|&d| d != UNUSED)
+            .map_or(1, |d| d + 1);
+
+        let mut s = f.debug_struct("Tensor");
+        s.field("dims", &&self.dims[..used]);
+        match &self.data {
+            Some(d) if d.len() > DEBUG_LEN => s.field("data", &Elided(&d[..DEBUG_LEN])),
+            Some(d) => s.field("data", d),
+            None => s.field("data", &None::<()>),
+        };
+        s.finish()
+    }
+}
+
+/// The first elements of a list which is too long to print in full,
+/// which prints as a list with a trailing `..`, e.g. `[1, 2, ..]`.
+struct Elided<'a, T>(&'a [T]);
+
+impl<T: fmt::Debug> fmt::Debug for Elided<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0)
+            .finish_non_exhaustive()
+    }
+}
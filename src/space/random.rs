@@ -0,0 +1,83 @@
+//! Random tensors, behind the `rand` feature. Since this crate is
+//! `no_std`, there's no thread-local source of entropy to fall back
+//! on, so every constructor takes an RNG, which makes it easy to get
+//! the same tensor each time from the same seed.
+
+use core::f64;
+use core::f64::consts::LN_2;
+
+use rand_core::RngCore;
+
+use super::{size, NzU16, Tensor};
+use crate::invar::Float;
+
+impl<T: Float> Tensor<T> {
+    /// Create a tensor of the given dims, filled with values drawn from
+    /// `rng`, uniformly over `[0, 1)`. See [`Tensor::random_uniform`].
+    pub fn random(dims: [NzU16; 8], rng: &mut impl RngCore) -> Tensor<T> {
+        Tensor::random_uniform(dims, T::default(), T::from_usize(1), rng)
+    }
+
+    /// Create a tensor of the given dims, filled with values drawn from
+    /// `rng`, uniformly over `[low, high)`. Each is drawn as an `f64`,
+    /// then rounded to `T`, so a narrow `T`, e.g. `f16`, may round up
+    /// to `high` itself, if it's close enough.
+    pub fn random_uniform(dims: [NzU16; 8], low: T, high: T, rng: &mut impl RngCore) -> Tensor<T> {
+        Tensor {
+            data: Some(
+                (0..size(&dims))
+                    .map(|_| low + (high - low) * T::from_f64(unit(rng)))
+                    .collect(),
+            ),
+            dims,
+        }
+    }
+
+    /// Create a tensor of the given dims, filled with values drawn from
+    /// `rng`, from the normal distribution of the given mean and standard
+    /// deviation. Each is drawn as an `f64`, then rounded to `T`.
+    pub fn random_normal(dims: [NzU16; 8], mean: T, std: T, rng: &mut impl RngCore) -> Tensor<T> {
+        Tensor {
+            data: Some(
+                (0..size(&dims))
+                    .map(|_| mean + std * T::from_f64(normal(rng)))
+                    .collect(),
+            ),
+            dims,
+        }
+    }
+}
+
+/// Draw an `f64` uniformly from `[0, 1)`, i.e. one of the 2^53 evenly
+/// spaced values in that range, which is every one an `f64` can hold
+/// at the precision of those nearest to 1.
+fn unit(rng: &mut impl RngCore) -> f64 {
+    (rng.next_u64() >> 11) as f64 * (1. / (1u64 << 53) as f64)
+}
+
+/// Draw an `f64` from the standard normal distribution, by the polar
+/// method, i.e. picking a point in the unit circle uniformly, and then
+/// scaling it. Unlike the Box-Muller transform, this needs no `cos`.
+fn normal(rng: &mut impl RngCore) -> f64 {
+    loop {
+        let (u, v) = (2. * unit(rng) - 1., 2. * unit(rng) - 1.);
+        let s = u * u + v * v;
+        if s > 0. && s < 1. {
+            return u * f64::math::sqrt(-2. * ln(s) / s);
+        }
+    }
+}
+
+/// The natural log of `x`, which must be positive and normal, since
+/// `core` has no `ln`. Splitting `x` into `m * 2^e`, where `m` is in
+/// `[1, 2)`, `ln x = e ln 2 + ln m`, and `ln m = 2 atanh((m-1)/(m+1))`,
+/// whose series converges quickly, as its argument is less than 1/3.
+fn ln(x: f64) -> f64 {
+    let bits = x.to_bits();
+    let e = ((bits >> 52) & 0x7FF) as i64 - 1023;
+    let m = f64::from_bits(bits & !(0x7FF << 52) | 1023 << 52);
+
+    let t = (m - 1.) / (m + 1.);
+    let (sum, _) = (0..20).fold((0., t), |(sum, tk), k| (sum + tk / (2 * k + 1) as f64, tk * t * t));
+    e as f64 * LN_2 + 2. * sum
+}
@@ -0,0 +1,87 @@
+//! `serde` impls for tensors, behind the `serde` feature. A tensor is
+//! serialised as a map of its `shape`, i.e. its dims up to its rank,
+//! and its `data`, in column-major order, or `null` if it has none.
+//! A matrix or vector is serialised the same as its inner tensor.
+
+use alloc::vec::Vec;
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{checked_size, Matrix, NzU16, Tensor, Vector, UNUSED};
+
+/// How a tensor is serialised. This borrows, so that serialising a
+/// tensor doesn't copy it.
+#[derive(Serialize)]
+struct TensorRef<'a, T> {
+    shape: &'a [NzU16],
+    data: Option<&'a [T]>,
+}
+
+/// How a tensor is deserialised, before its shape is checked.
+#[derive(Deserialize)]
+struct TensorRepr<T> {
+    shape: Vec<NzU16>,
+    data: Option<Vec<T>>,
+}
+
+impl<T: Serialize> Serialize for Tensor<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        TensorRef { shape: &self.dims[..self.rank()], data: self.data.as_deref() }.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Tensor<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = TensorRepr::<T>::deserialize(deserializer)?;
+        if repr.shape.len() > 8 {
+            return Err(D::Error::custom("tensors have at most 8 dims"));
+        }
+
+        let mut dims = [UNUSED; 8];
+        dims[..repr.shape.len()].copy_from_slice(&repr.shape);
+        let len =
+            checked_size(&dims).ok_or_else(|| D::Error::custom("shape has too many elements to count"))?;
+        if let Some(d) = &repr.data {
+            if d.len() != len {
+                return Err(D::Error::invalid_length(d.len(), &"as many elements as the shape calls for"));
+            }
+        }
+
+        Ok(Tensor { data: repr.data, dims })
+    }
+}
+
+impl<T: Serialize> Serialize for Matrix<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Matrix<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let t = Tensor::deserialize(deserializer)?;
+        if t.rank() > 2 {
+            return Err(D::Error::custom("matrices have at most 2 dims"));
+        }
+
+        Ok(Matrix(t))
+    }
+}
+
+impl<T: Serialize> Serialize for Vector<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Vector<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let t = Tensor::deserialize(deserializer)?;
+        if t.rank() > 1 {
+            return Err(D::Error::custom("vectors have at most 1 dim"));
+        }
+
+        Ok(Vector(t))
+    }
+}
@@ -0,0 +1,138 @@
+//! Statically sized matrices, whose dims are part of their type, so
+//! that they live on the stack, and mismatched dims fail to compile.
+
+use core::ops;
+
+use super::{shape, try_dim, Matrix, Tensor, UNUSED};
+use crate::invar::{One, Scalar, Zero};
+
+/// An HxW matrix whose dims are known at compile time, e.g. a 4x4
+/// transform, stored inline, column-major as a [`Matrix`] is, i.e. as
+/// `W` columns of `H` elements each. Nothing is allocated, so these
+/// are cheap to create and copy, but unlike a [`Matrix`], they're
+/// never multiplied on AMX, as that's only worth it at larger sizes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SMatrix<T, const H: usize, const W: usize>(pub(crate) [[T; H]; W]);
+
+impl<T, const H: usize, const W: usize> SMatrix<T, H, W> {
+    /// Asserts that H and W are both valid dims, i.e. in `1..=65535`.
+    /// This is only evaluated where it's used, at compile time.
+    const TENSOR_DIMS: () = assert!(
+        H >= 1 && H <= u16::MAX as usize && W >= 1 && W <= u16::MAX as usize,
+        "an SMatrix can only become a tensor if H and W are in 1..=65535"
+    );
+
+    /// Create a new [`SMatrix`] from an array of its columns, i.e. in
+    /// column-major order, as it's stored, so this moves it as it is.
+    pub const fn from_cols(cols: [[T; H]; W]) -> Self {
+        SMatrix(cols)
+    }
+
+    /// Borrow the columns of this matrix, in order.
+    pub fn cols(&self) -> &[[T; H]; W] {
+        &self.0
+    }
+}
+
+impl<T: Copy, const H: usize, const W: usize> SMatrix<T, H, W> {
+    /// Create a new [`SMatrix`] from an array of its rows, which is how
+    /// a matrix is written out by hand, e.g. `[[1, 2], [3, 4]]`.
+    pub fn from_rows(rows: [[T; W]; H]) -> Self {
+        SMatrix(core::array::from_fn(|j| core::array::from_fn(|i| rows[i][j])))
+    }
+
+    /// Transpose this matrix, so an HxW matrix becomes WxH.
+    pub fn transpose(&self) -> SMatrix<T, W, H> {
+        SMatrix(core::array::from_fn(|i| core::array::from_fn(|j| self.0[j][i])))
+    }
+
+    /// Copy a tensor off the heap into an [`SMatrix`], if it's an HxW
+    /// matrix with data, e.g. to work on a small one without allocating,
+    /// or `None` if it isn't. [`SMatrix::to_tensor`] is the inverse.
+    pub fn from_tensor(t: &Tensor<T>) -> Option<Self> {
+        let data = t.data()?;
+        // No tensor can be HxW if either is out of range for a dim.
+        let mut dims = [UNUSED; 8];
+        (dims[0], dims[1]) = (try_dim(H).ok()?, try_dim(W).ok()?);
+        if t.dims != dims {
+            return None;
+        }
+
+        Some(SMatrix(core::array::from_fn(|j| core::array::from_fn(|i| data[j * H + i]))))
+    }
+
+    /// Copy a matrix off the heap into an [`SMatrix`], if it's HxW and
+    /// has data, or `None` if not. See [`SMatrix::from_tensor`].
+    pub fn from_matrix(m: &Matrix<T>) -> Option<Self> {
+        SMatrix::from_tensor(&m.0)
+    }
+
+    /// Copy this matrix onto the heap, as a [`Tensor`] of dims HxW.
+    /// Both must be valid dims, i.e. in `1..=65535`, or this won't
+    /// compile, since there's no tensor of that shape to return.
+    pub fn to_tensor(&self) -> Tensor<T> {
+        let () = Self::TENSOR_DIMS;
+
+        Tensor {
+            data: Some(self.0.iter().flatten().copied().collect()),
+            dims: shape(&[H, W]),
+        }
+    }
+
+    /// Copy this matrix onto the heap, as a [`Matrix`] of the same dims,
+    /// e.g. to use it with the ops only defined for those.
+    pub fn to_matrix(&self) -> Matrix<T> {
+        Matrix(self.to_tensor())
+    }
+}
+
+impl<T: Scalar + Zero, const H: usize, const K: usize> SMatrix<T, H, K> {
+    /// Multiply this matrix by another matrix, returning the product.
+    /// An HxK matrix may only be multiplied by a KxW matrix, and the
+    /// product is HxW, as for [`Matrix::multiply`], but here that's
+    /// checked by the compiler, so there's nothing to go wrong at run
+    /// time. The product is taken with the textbook triple loop.
+    pub fn multiply<const W: usize>(&self, rhs: &SMatrix<T, K, W>) -> SMatrix<T, H, W> {
+        SMatrix(core::array::from_fn(|j| {
+            core::array::from_fn(|i| (0..K).fold(T::zero(), |acc, k| acc + self.0[k][i] * rhs.0[j][k]))
+        }))
+    }
+}
+
+impl<T: Zero + One, const N: usize> SMatrix<T, N, N> {
+    /// Create the NxN identity matrix, i.e. with 1s on the diagonal and
+    /// 0s everywhere else, as [`Matrix::identity`] does.
+    pub fn identity() -> Self {
+        SMatrix(core::array::from_fn(|j| {
+            core::array::from_fn(|i| if i == j { T::one() } else { T::zero() })
+        }))
+    }
+}
+
+impl<T: Scalar + Zero, const H: usize, const K: usize, const W: usize> ops::Mul<SMatrix<T, K, W>>
+    for SMatrix<T, H, K>
+{
+    type Output = SMatrix<T, H, W>;
+
+    /// The matrix product, as [`SMatrix::multiply`] returns.
+    fn mul(self, rhs: SMatrix<T, K, W>) -> Self::Output {
+        self.multiply(&rhs)
+    }
+}
+
+impl<T, const H: usize, const W: usize> ops::Index<(usize, usize)> for SMatrix<T, H, W> {
+    type Output = T;
+
+    /// Borrow the element at `(row, col)`. This panics if either is out
+    /// of range, as indexing into an array does.
+    fn index(&self, (row, col): (usize, usize)) -> &T {
+        &self.0[col][row]
+    }
+}
+
+impl<T, const H: usize, const W: usize> ops::IndexMut<(usize, usize)> for SMatrix<T, H, W> {
+    /// Mutably borrow the element at `(row, col)`. See [`ops::Index`].
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut T {
+        &mut self.0[col][row]
+    }
+}
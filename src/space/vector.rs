@@ -1,16 +1,67 @@
 use alloc::vec::Vec;
+use core::fmt;
 
-use super::Tensor;
+use super::{dim, display_elem, display_indices, try_dim, LenErr, Tensor, UNUSED};
 
-pub struct Vector<T>(Tensor<T>);
+#[derive(Clone, PartialEq)]
+pub struct Vector<T>(pub(crate) Tensor<T>);
+
+impl<T: fmt::Debug> fmt::Debug for Vector<T> {
+    /// Prints the inner [`Tensor`], as its [`fmt::Debug`] impl does.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Vector<T> {
+    /// Prints the vector on one line, as NumPy does, e.g. `[1, 2, 3]`.
+    /// A vector too long to print in full is cut down to its ends, with
+    /// `...` in place of the rest.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(data) = self.0.data() else {
+            return write!(f, "[vector of {} without data]", self.0.vlen());
+        };
+
+        f.write_str("[")?;
+        for (n, i) in display_indices(data.len())
+            .into_iter()
+            .enumerate()
+        {
+            if n > 0 {
+                f.write_str(", ")?;
+            }
+            match i {
+                Some(i) => f.write_str(&display_elem(&data[i], f))?,
+                None => f.write_str("...")?,
+            }
+        }
+        f.write_str("]")
+    }
+}
 
 impl<T> Vector<T> {
     /// Create a new [`Vector`] from a plain Rust [`Vec`]. Note: This
-    /// consumes the vector that you pass in.
-    pub fn from(arr: Vec<T>) -> Self {
-        Vector(Tensor {
-            data: Some(arr),
-            dims: [arr.len() as u16, 0, 0, 0, 0, 0, 0, 0],
-        })
+    /// consumes the vector that you pass in. Since a vector's length
+    /// is a single dimension, it must be in `1..=65535`, or this will
+    /// return a [`LenErr`].
+    pub fn from(arr: Vec<T>) -> Result<Self, LenErr> {
+        let mut dims = [UNUSED; 8];
+        dims[0] = try_dim(arr.len())?;
+
+        Ok(Vector(Tensor { data: Some(arr), dims }))
+    }
+}
+
+impl<T> FromIterator<T> for Vector<T> {
+    /// Collect the elements of an iterator into a [`Vector`], in order.
+    /// Since a vector's length must be in `1..=65535`, this panics if
+    /// the iterator yields no elements, or more than that. If that's
+    /// possible, collect into a [`Vec`], and call [`Vector::from`].
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let data: Vec<T> = iter.into_iter().collect();
+        let mut dims = [UNUSED; 8];
+        dims[0] = dim(data.len());
+
+        Vector(Tensor { data: Some(data), dims })
     }
 }
@@ -0,0 +1,190 @@
+use core::fmt;
+use core::ops::Range;
+
+use super::{size, strides, try_dim, NzU16, PermuteErr, SliceErr, Tensor, UNUSED};
+
+/// A borrowed, possibly non-contiguous, view of a [`Tensor`]. Where a
+/// tensor's layout is implied by its dims, a view's is explicit: each
+/// dim has its own stride, and the view starts at an offset into the
+/// data. So transposing, permuting, or slicing a view only rearranges
+/// those, and copies nothing, until [`TensorView::to_owned`] is called.
+pub struct TensorView<'a, T> {
+    data: Option<&'a [T]>,
+    dims: [NzU16; 8],
+    strides: [usize; 8],
+    offset: usize,
+}
+
+impl<T> Clone for TensorView<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for TensorView<'_, T> {}
+
+impl<T> Tensor<T> {
+    /// Borrow this tensor as a [`TensorView`], whose strides are those
+    /// of its own (column-major) layout, so it can be rearranged lazily.
+    pub fn view(&self) -> TensorView<'_, T> {
+        TensorView {
+            data: self.data.as_deref(),
+            dims: self.dims,
+            strides: strides(&self.dims),
+            offset: 0,
+        }
+    }
+}
+
+impl<'a, T> TensorView<'a, T> {
+    /// Get the dimensions of this view.
+    pub fn dims(&self) -> [NzU16; 8] {
+        self.dims
+    }
+
+    /// Get the strides of this view, i.e. how far apart in the data
+    /// consecutive elements of each dim are.
+    pub fn strides(&self) -> [usize; 8] {
+        self.strides
+    }
+
+    /// Whether the elements of this view are laid out contiguously, in
+    /// column-major order, i.e. as a tensor of its dims would be, which
+    /// is what e.g. AMX loads assume. A fresh view is, but a transposed
+    /// one generally isn't, nor is a slice of anything but the last dim.
+    /// The strides of dims of length 1 don't matter, as they're unused.
+    pub fn is_contiguous(&self) -> bool {
+        let expected = strides(&self.dims);
+        (0..8).all(|d| self.dims[d] == UNUSED || self.strides[d] == expected[d])
+    }
+
+    /// Borrow the elements of this view as a slice, in column-major order,
+    /// if they're contiguous, so that they can be used without a copy.
+    /// Returns `None` if they aren't, or there's no data: in that case,
+    /// [`TensorView::to_owned`] makes a contiguous copy.
+    pub fn as_contiguous(&self) -> Option<&'a [T]> {
+        let data = self.data?;
+        self.is_contiguous()
+            .then(|| &data[self.offset..self.offset + size(&self.dims)])
+    }
+
+    /// Borrow the element at the coordinates `idx`, one per dim. This
+    /// works in exactly the same way as [`Tensor::get`], but goes by
+    /// this view's strides, rather than the layout of the data.
+    pub fn get(&self, idx: &[usize]) -> Option<&'a T> {
+        if idx.len() > 8 {
+            return None;
+        }
+
+        let j = idx
+            .iter()
+            .zip(self.dims.iter().zip(self.strides))
+            .try_fold(self.offset, |acc, (&i, (&d, stride))| {
+                (i < d.get() as usize).then(|| acc + i * stride)
+            })?;
+        self.data?.get(j)
+    }
+
+    /// Iterate over the elements of this view, in column-major order,
+    /// as though it were a tensor of its own. A view of a tensor with
+    /// no data yields nothing.
+    pub fn iter(&self) -> impl Iterator<Item = &'a T> + 'a {
+        let (dims, from, offset) = (self.dims, self.strides, self.offset);
+        let to = strides(&dims);
+        let data = self.data.unwrap_or(&[]);
+
+        (0..if self.data.is_some() { size(&dims) } else { 0 }).map(move |i| {
+            let j = (0..8)
+                .map(|d| i / to[d] % dims[d].get() as usize * from[d])
+                .sum::<usize>();
+            &data[offset + j]
+        })
+    }
+
+    /// Swap the first two dims of this view, i.e. transpose it, if it's
+    /// a matrix, as [`Matrix::transpose`](crate::Matrix::transpose) does,
+    /// but without moving any data.
+    pub fn transpose(mut self) -> Self {
+        self.dims.swap(0, 1);
+        self.strides.swap(0, 1);
+        self
+    }
+
+    /// Reorder the dims of this view, so that dim `k` of the result is
+    /// dim `order[k]` of this one, as [`Tensor::permute`] does, but
+    /// without moving any data. `order` is checked in the same way.
+    pub fn permute(self, order: &[usize]) -> Result<Self, PermuteErr> {
+        if order.len() > 8 {
+            return Err(PermuteErr::TooManyDims(order.len()));
+        }
+
+        let mut seen = [false; 8];
+        for &d in order {
+            if d >= order.len() || seen[d] {
+                return Err(PermuteErr::NotPermutation);
+            }
+            seen[d] = true;
+        }
+
+        // Dim `k` of the result walks dim `order[k]` of this view, so it
+        // takes that dim's length, and that dim's stride to read by.
+        let (mut dims, mut strides) = (self.dims, self.strides);
+        order.iter().enumerate().for_each(|(k, &d)| {
+            dims[k] = self.dims[d];
+            strides[k] = self.strides[d];
+        });
+
+        Ok(TensorView { dims, strides, ..self })
+    }
+
+    /// Narrow this view to the sub-tensor covering `ranges`, one range
+    /// per dim, as [`Tensor::slice`] does, but without copying any data:
+    /// the view just starts further in. The ranges are checked likewise.
+    pub fn slice(self, ranges: &[Range<usize>]) -> Result<Self, SliceErr> {
+        if ranges.len() > 8 {
+            return Err(SliceErr::TooManyRanges(ranges.len()));
+        }
+
+        let (mut dims, mut offset) = (self.dims, self.offset);
+        for (d, r) in ranges.iter().enumerate() {
+            let len = self.dims[d].get() as usize;
+            if r.end > len {
+                return Err(SliceErr::OutOfRange { dim: d, range: r.clone(), len });
+            }
+            dims[d] = try_dim(r.len()).map_err(|_| SliceErr::Empty { dim: d })?;
+            offset += r.start * self.strides[d];
+        }
+
+        Ok(TensorView { dims, offset, ..self })
+    }
+}
+
+impl<T: Clone> TensorView<'_, T> {
+    /// Copy the elements of this view into a new tensor of its dims, in
+    /// column-major order, i.e. materialise it. If the view is of a
+    /// tensor with no data, so is the result.
+    pub fn to_owned(&self) -> Tensor<T> {
+        Tensor {
+            data: self.data.map(|_| self.iter().cloned().collect()),
+            dims: self.dims,
+        }
+    }
+}
+
+impl<T> fmt::Debug for TensorView<'_, T> {
+    /// Prints the dims and strides, but not the data, as the data is
+    /// only borrowed. Call [`TensorView::to_owned`] to print that.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let used = self
+            .dims
+            .iter()
+            .rposition(|&d| d != UNUSED)
+            .map_or(1, |d| d + 1);
+
+        f.debug_struct("TensorView")
+            .field("dims", &&self.dims[..used])
+            .field("strides", &&self.strides[..used])
+            .field("offset", &self.offset)
+            .finish_non_exhaustive()
+    }
+}